@@ -0,0 +1,154 @@
+use shakmaty::{CastlingMode, Chess};
+use shakmaty::fen::Fen;
+use std::fs;
+use std::time::Instant;
+use crate::engine::{Engine, SearchLimits};
+use crate::params::EvalMode;
+
+pub const DEFAULT_BENCH_DEPTH: u32 = 8;
+
+// Out-of-book middlegame and endgame positions so every entry is actually searched
+pub const BENCH_POSITIONS: &[&str] = &[
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N2N2/PP2BPPP/R2QKB1R w KQ - 0 8",
+    "r2q1rk1/1b1nbppp/pp1ppn2/8/2PNP3/1PN1B3/P3BPPP/R2Q1RK1 w - - 0 11",
+    "2r2rk1/pp1bqppp/2n1pn2/3p4/3P4/2PBPN2/P1Q2PPP/R4RK1 b - - 0 14",
+    "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "6k1/5p2/6p1/8/7p/8/6PP/6K1 b - - 0 1",
+    "8/8/3k4/8/2PK4/8/8/8 w - - 0 1",
+];
+
+// Node counts per bench position at one depth, as saved by `bench save` and compared by `bench check`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Baseline {
+    pub depth: u32,
+    pub nodes: Vec<(String, u64)>,
+}
+
+impl Baseline {
+    // `depth <n>` on the first line, then `<nodes> <fen>` per position
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines().enumerate().filter(|(_, l)| !l.trim().is_empty());
+        let depth = lines.next()
+            .and_then(|(_, l)| l.trim().strip_prefix("depth "))
+            .and_then(|d| d.trim().parse().ok())
+            .ok_or("line 1: expected 'depth <n>'")?;
+        let nodes = lines.map(|(n, line)| {
+            let (nodes, fen) = line.trim().split_once(' ').ok_or_else(|| format!("line {}: expected '<nodes> <fen>'", n + 1))?;
+            let nodes = nodes.parse().map_err(|_| format!("line {}: bad node count '{}'", n + 1, nodes))?;
+            Ok((fen.trim().to_string(), nodes))
+        }).collect::<Result<_, String>>()?;
+        Ok(Self { depth, nodes })
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!("depth {}\n", self.depth);
+        for (fen, nodes) in &self.nodes {
+            text.push_str(&format!("{} {}\n", nodes, fen));
+        }
+        text
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        Self::parse(&text).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        fs::write(path, self.to_text()).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    // One line per position whose count moved, or that only one side has. Empty when the trees match.
+    pub fn compare(&self, current: &Baseline) -> Vec<String> {
+        let mut changes = Vec::new();
+        for (fen, old) in &self.nodes {
+            match current.nodes.iter().find(|(f, _)| f == fen) {
+                Some((_, new)) if new != old => {
+                    let pct = (*new as f64 - *old as f64) * 100.0 / (*old).max(1) as f64;
+                    changes.push(format!("{} nodes {} -> {} ({:+.1}%)", fen, old, new, pct));
+                }
+                Some(_) => {}
+                None => changes.push(format!("{} missing from this run", fen)),
+            }
+        }
+        for (fen, _) in current.nodes.iter().filter(|(fen, _)| !self.nodes.iter().any(|(f, _)| f == fen)) {
+            changes.push(format!("{} not in the baseline", fen));
+        }
+        changes
+    }
+}
+
+// Searches every bench position at a fixed depth and reports total nodes and speed.
+pub fn run(depth: u32, eval_mode: EvalMode) -> Baseline {
+    let mut engine = Engine::new();
+    engine.set_eval_mode(eval_mode);
+    let mut nodes = Vec::new();
+    let start = Instant::now();
+
+    for fen in BENCH_POSITIONS {
+        let pos: Chess = fen.parse::<Fen>().expect("valid bench FEN")
+            .into_position(CastlingMode::Standard).expect("legal bench position");
+        engine.find_best_move(&pos, &SearchLimits::depth(depth));
+        nodes.push((fen.to_string(), engine.nodes()));
+    }
+
+    let total_nodes: u64 = nodes.iter().map(|(_, n)| n).sum();
+    let ms = start.elapsed().as_millis() as u64;
+    println!("bench depth {} nodes {} time {} nps {}", depth, total_nodes, ms, total_nodes * 1000 / ms.max(1));
+    Baseline { depth, nodes }
+}
+
+// `bench [depth] [full|material]`, `bench save <file> [depth]` or `bench check <file>`, the last
+// searching at the baseline's depth. Ok(false) when the check found a changed node count.
+pub fn command(args: &[&str]) -> Result<bool, String> {
+    match args.first() {
+        Some(&"save") => {
+            let path = args.get(1).ok_or("usage: bench save <file> [depth]")?;
+            let depth = args.get(2).and_then(|d| d.parse().ok()).unwrap_or(DEFAULT_BENCH_DEPTH);
+            run(depth, EvalMode::Full).save(path)?;
+            Ok(true)
+        }
+        Some(&"check") => {
+            let path = args.get(1).ok_or("usage: bench check <file>")?;
+            let baseline = Baseline::load(path)?;
+            let changes = baseline.compare(&run(baseline.depth, EvalMode::Full));
+            for change in &changes {
+                println!("changed {}", change);
+            }
+            println!("bench check {}", if changes.is_empty() { "ok" } else { "failed" });
+            Ok(changes.is_empty())
+        }
+        _ => {
+            let depth = args.first().and_then(|d| d.parse().ok()).unwrap_or(DEFAULT_BENCH_DEPTH);
+            let mode = args.get(1).and_then(|m| EvalMode::parse(m)).unwrap_or(EvalMode::Full);
+            run(depth, mode);
+            Ok(true)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn baseline_round_trips_and_reports_changes() {
+        let baseline = Baseline { depth: 3, nodes: vec![(BENCH_POSITIONS[0].to_string(), 1200), (BENCH_POSITIONS[7].to_string(), 80)] };
+        let path = std::env::temp_dir().join(format!("betterfish-bench-{}.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+        baseline.save(path).unwrap();
+        let loaded = Baseline::load(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(loaded, baseline);
+        assert!(loaded.compare(&baseline).is_empty());
+
+        let changed = Baseline { depth: 3, nodes: vec![(BENCH_POSITIONS[0].to_string(), 1500), (BENCH_POSITIONS[7].to_string(), 80)] };
+        let changes = baseline.compare(&changed);
+        assert_eq!(changes.len(), 1);
+        assert!(changes[0].contains("1200 -> 1500"), "{}", changes[0]);
+
+        assert!(Baseline::parse("80 8/8/3k4/8/2PK4/8/8/8 w - - 0 1").is_err());
+        assert!(Baseline::parse("depth 3\nlots 8/8/3k4/8/2PK4/8/8/8 w - - 0 1").is_err());
+    }
+}
@@ -1,5 +1,3 @@
-use shakmaty::Role;
-
 // Middlegame PSTs (PeSTO-inspired)
 pub const MG_PAWN_PST: [i32; 64] = [
       0,   0,   0,   0,   0,   0,   0,   0,
@@ -90,21 +88,71 @@ pub const EG_KING_PST: [i32; 64] = [
     -53, -34, -21, -11, -28, -14, -24, -43
 ];
 
-pub fn get_material_value(role: Role) -> i32 {
-    match role {
-        Role::Pawn => 100,
-        Role::Knight => 320,
-        Role::Bishop => 330,
-        Role::Rook => 500,
-        Role::Queen => 900,
-        Role::King => 20000,
-    }
-}
+// Pawn, knight, bishop, rook, queen; overridden at runtime with the PieceValues option
+pub const PIECE_VALUES: [i32; 5] = [100, 320, 330, 500, 900];
+pub const KING_VALUE: i32 = 20000;
 
 pub const PASSED_PAWN_BONUS: [i32; 8] = [0, 10, 20, 40, 70, 120, 200, 0];
+// Taken off a passed pawn with an enemy piece on its stop square, by blockader role (pawn..king).
+// Knights blockade best: they lose no mobility sitting there and still cover squares around it.
+pub const BLOCKADER_BONUS: [i32; 6] = [0, 25, 15, 5, 0, 15];
+// Per square a piece attacks that isn't ours, by role (pawn..king), tapered like the PSTs.
+// The king only counts in the endgame, where it becomes an active piece.
+pub const MOBILITY_MG: [i32; 6] = [0, 4, 5, 2, 1, 0];
+pub const MOBILITY_EG: [i32; 6] = [0, 3, 4, 4, 2, 4];
+// Per step a piece is closer to the enemy king than the far corner, by role (pawn..king), middlegame only
+pub const TROPISM_WEIGHT: [i32; 6] = [0, 3, 1, 1, 4, 0];
+pub const PAWN_STORM_BONUS: [i32; 8] = [0, 0, 0, 10, 20, 30, 15, 0];
+pub const SHIELD_HOLE_PENALTY: i32 = -15;
 pub const ISOLATED_PAWN_PENALTY: i32 = -15;
-pub const DOUBLED_PAWN_PENALTY: i32 = -10;
+pub const CHAIN_BASE_PENALTY: i32 = -12; // unsupported rear pawn of a diagonal chain whose front is blocked
+pub const PAWN_TENSION_BONUS: i32 = 5; // per pair of pawns able to take each other, to the side with more space
+// Per pawn on a doubled file, by file: centre doubles still control central squares
+pub const DOUBLED_PAWN_PENALTY: [i32; 8] = [-14, -12, -8, -6, -6, -8, -12, -14];
+pub const ISOLATED_DOUBLED_PENALTY: i32 = -10; // on top of both penalties: nothing can ever undouble them
+pub const DOUBLED_PAWN_ROOK_COMPENSATION: i32 = 5; // our rook on an adjacent file with none of our pawns
 pub const ROOK_ON_OPEN_FILE: i32 = 25;
+pub const ROOK_ON_SEMI_OPEN_FILE: i32 = 12; // both file bonuses halved when an enemy rook is on the file
+pub const ROOK_ATTACKS_WEAK_PAWN: i32 = 15; // isolated or backward enemy pawn in the rook's line down the file
 pub const KING_SHIELD_BONUS: i32 = 10;
+pub const FIANCHETTO_BONUS: i32 = 5; // advanced g/b-pawn with its bishop behind: partly makes up the lost shield pawn
+pub const FIANCHETTO_HOLE_PENALTY: i32 = -20; // same pawn advance with the fianchetto bishop gone
+pub const KING_FLIGHT_PENALTY: i32 = -10; // per safe king move short of KING_FLIGHT_SQUARES, middlegame only
+pub const KING_FLIGHT_SQUARES: u32 = 2;
+pub const KING_SEMI_OPEN_FILE_PENALTY: i32 = -10; // per king or adjacent file without a friendly pawn
+pub const KING_OPEN_FILE_PENALTY: i32 = -20; // same, with no pawns at all; both doubled when an enemy rook/queen is on the file
 pub const ROOK_ON_7TH_BONUS: i32 = 40;
+pub const HEAVY_INFILTRATION_BONUS: i32 = 10; // times the square of the rooks and queens on the enemy's 7th and 8th ranks
+pub const CONFINED_KING_FACTOR: i32 = 2; // infiltration bonus multiplier when the enemy king is on those ranks too
+pub const ROOK_BEHIND_PASSER: i32 = 20; // own or enemy passed pawn ahead of the rook on its file
 pub const KNIGHT_OUTPOST_BONUS: i32 = 30;
+pub const BISHOP_OUTPOST_BONUS: i32 = 15;
+pub const KNIGHT_RIM_PENALTY: i32 = -15;
+pub const BAD_BISHOP_PENALTY: i32 = -4; // per friendly pawn on the bishop's square colour
+pub const PAWN_MAJORITY_BONUS: i32 = 15; // per wing (a-c, f-h) with more pawns than the enemy on distinct files
+pub const MINORITY_ATTACK_BONUS: i32 = 10; // per wing where the fewer pawns have already advanced to the 4th rank
+pub const MOP_UP_EDGE: i32 = 10; // per step of the lone king away from the centre
+pub const MOP_UP_KINGS: i32 = 4; // per step our king is closer than the far corner
+pub const STALEMATE_RISK_PENALTY: i32 = -60; // lone king with no moves and not in check
+pub const BISHOP_OPEN_BONUS: i32 = 12; // per bishop at 0 pawns, falling to a penalty as the board closes
+pub const KNIGHT_CLOSED_BONUS: i32 = 12; // per knight at 16 pawns (locked pairs count double), a penalty when open
+pub const CONNECTIVITY_BONUS: i32 = 8; // per minor defended by a pawn and for rooks connected on the back rank, middlegame only
+pub const DEVELOPED_MINOR_BONUS: i32 = 10; // per knight or bishop off its starting square, opening only
+pub const CASTLED_BONUS: i32 = 25; // king off the e-file on its back rank with no rook shut in beside the corner
+pub const EARLY_QUEEN_PENALTY: i32 = -20; // queen off its starting square: per minor still at home beyond the first
+pub const DEVELOPMENT_PHASE_END: i32 = 64; // game phase (0-256) at which the development terms have faded out
+pub const BISHOP_PAIR_BONUS: i32 = 50;
+pub const LOCKED_BISHOP_PAIR_BONUS: i32 = 20;
+pub const LOCKED_PAWN_PAIRS: usize = 3; // head-to-head pawn pairs before the position counts as locked
+pub const FORTRESS_LOCKED_PAIRS: usize = 4; // fewest head-to-head pawn pairs for a blockade
+pub const FIFTY_MOVE_SCALE_START: u32 = 40; // halfmove clock at which the eval starts shrinking toward 0
+pub const HISTORY_MAX: i32 = 16384; // history entries stay within ±this, bonuses shrinking as they near it
+pub const HISTORY_BONUS_MAX: i32 = 1200; // cap on the depth * depth bonus (or malus) from one cutoff
+pub const ASPIRATION_DELTA: i32 = 50; // half-width of the root search window once depth is high
+// Endgame scale factors, out of 256
+pub const SCALE_NORMAL: i32 = 256;
+pub const SCALE_OPPOSITE_BISHOPS: i32 = 128;
+pub const SCALE_DRAWISH: i32 = 32;
+pub const SCALE_BLOCKADE: i32 = 128; // pawns locked, no lever and no file for the stronger side's rooks
+pub const SCALE_DRAW: i32 = 0; // known dead draw: the eval returns 0 outright
+pub const QUEEN_VS_PAWN_KING_DISTANCE: u32 = 3; // KQvKP on the 7th: an attacking king this close to the pawn still wins
@@ -1,72 +1,635 @@
-use shakmaty::{Chess, Move, Position, MoveList, Role, Color};
-use shakmaty::zobrist::{ZobristHash, Zobrist64};
-use crate::evaluation::evaluate;
-use crate::tt::{TTEntry, NodeType};
-use crate::constants::get_material_value;
+use shakmaty::{attacks, Bitboard, CastlingMode, Chess, Move, Position, MoveList, Outcome, Role, Color};
+use shakmaty::san::SanPlus;
+use crate::evaluation::{evaluate, evaluate_is_symmetric};
+use crate::hash::{full_hash, hash_after, hash_after_null};
+use crate::tt::{NodeType, TTEntry, TTStats, TranspositionTable};
+use crate::constants::{HISTORY_BONUS_MAX, HISTORY_MAX};
 use crate::opening_book::OpeningBook;
-use std::collections::HashMap;
+use crate::params::{CaptureOrdering, EvalKind, EvalMode, EvalParams, PstSet, SearchParams};
+use crate::rng::Rng;
+use crate::uci_log;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+// In-progress info lines are throttled to this; per-depth lines are always sent.
+const INFO_INTERVAL: Duration = Duration::from_millis(500);
+
+pub const DEFAULT_CONTEMPT: i32 = 0;
+
+pub const DEFAULT_DEPTH: u32 = 6;
+pub const DEFAULT_HASH_MB: usize = 64;
+pub const MAX_HASH_MB: usize = 4096;
+pub const MAX_THREADS: usize = 64;
+// BookDepth: plies into the game the book is consulted for; the default never gets in its way
+pub const DEFAULT_BOOK_DEPTH: u32 = 255;
+// SlowMover: percent of the base time allocation to use
+pub const DEFAULT_SLOW_MOVER: u64 = 100;
+pub const MAX_SLOW_MOVER: u64 = 1000;
+// Killer tables are sized for this, so it also bounds the MaxDepth option
+pub const MAX_DEPTH: u32 = 64;
+
+// Milliseconds kept back from every allocation for GUI and pipe latency
+const MOVE_OVERHEAD_MS: u64 = 50;
+// Moves the remaining clock is spread over when the GUI doesn't say
+const DEFAULT_MOVES_TO_GO: u64 = 30;
+
+// Every limit given applies at once: the search ends at whichever of depth, nodes or time
+// runs out first. Nodes and time are polled mid-iteration, depth between iterations.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchLimits {
+    pub depth: u32,
+    // Empty = all legal moves
+    pub search_moves: Vec<Move>,
+    // Only the stop flag ends the search; bestmove is held back until then
+    pub infinite: bool,
+    // Checked alongside the stop flag, so it may overshoot by up to one polling interval
+    pub nodes: Option<u64>,
+    // Clock and increments in milliseconds, as sent with `go`
+    pub movetime: Option<u64>,
+    pub wtime: Option<u64>,
+    pub btime: Option<u64>,
+    pub winc: u64,
+    pub binc: u64,
+    pub movestogo: Option<u32>,
+}
+
+impl Default for SearchLimits {
+    fn default() -> Self {
+        Self {
+            depth: DEFAULT_DEPTH,
+            search_moves: Vec::new(),
+            infinite: false,
+            nodes: None,
+            movetime: None,
+            wtime: None,
+            btime: None,
+            winc: 0,
+            binc: 0,
+            movestogo: None,
+        }
+    }
+}
+
+impl SearchLimits {
+    pub fn depth(depth: u32) -> Self {
+        Self { depth, ..Self::default() }
+    }
+
+    // Search time in milliseconds for the side to move: all of movetime, otherwise an even share
+    // of the clock over the moves left to the next time control (movestogo, or a guess) plus the
+    // increment, scaled by the SlowMover percentage. None when there is no clock or the search
+    // is infinite.
+    pub fn allocate_time(&self, turn: Color, slow_mover: u64) -> Option<u64> {
+        if self.infinite { return None; }
+        if let Some(movetime) = self.movetime {
+            return Some(movetime.saturating_sub(MOVE_OVERHEAD_MS).max(1));
+        }
+        let (time, inc) = match turn {
+            Color::White => (self.wtime?, self.winc),
+            Color::Black => (self.btime?, self.binc),
+        };
+        let moves_to_go = self.movestogo.map_or(DEFAULT_MOVES_TO_GO, |n| n.max(1) as u64);
+        let budget = (time / moves_to_go + inc) * slow_mover / 100;
+        Some(budget.min(time.saturating_sub(MOVE_OVERHEAD_MS)).max(1))
+    }
+}
+
+#[derive(Clone)]
+struct RootLine {
+    score: i32,
+    pv: Vec<Move>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PvNotation {
+    Uci,
+    San,
+}
+
+// QSearch option. Shallow looks only at captures worth at least qsearch_shallow_min_gain, trading
+// tactical accuracy for speed in bullet
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum QSearchMode {
+    Full,
+    Shallow,
+}
+
+// UCI scores are from the side to move; White's view is easier on humans reading a log
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ScorePerspective {
+    SideToMove,
+    White,
+}
+
+// A quick, searchless verdict on one move for GUIs annotating the move list. Both numbers are
+// in centipawns from White's point of view.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MoveAssessment {
+    // Material won or lost by the exchange the move starts on its target square
+    pub see: i32,
+    // Static eval of the position after the move
+    pub eval_after: i32,
+}
 
 pub struct Engine {
-    tt: HashMap<u64, TTEntry>,
+    tt: TranspositionTable,
+    hash_mb: usize,
+    // Validated Threads option; only one search thread exists so far
+    threads: usize,
     killers: [[Option<Move>; 2]; 64],
-    history: [[[u32; 64]; 64]; 2],
+    // Quiet move scores by side, from and to square: raised by cutoffs, lowered for the quiets
+    // searched before a cutoff that failed to cause one
+    history: [[[i32; 64]; 64]; 2],
     book: OpeningBook,
+    book_depth: u32,
+    // Master PRNG: every random choice (book picks so far) draws its own stream from it
+    rng: Rng,
+    eval_params: EvalParams,
+    // Centipawns the engine gives up to avoid a draw; positive = play for a win
+    contempt: i32,
+    // UCI_DrawScore: an absolute draw value for the root side. Replaces contempt once set
+    draw_score: Option<i32>,
+    search_params: SearchParams,
+    pv_notation: PvNotation,
+    qsearch_mode: QSearchMode,
+    score_perspective: ScorePerspective,
+    // Enables the chattier `info string` diagnostics. Shared like `stop`, so `debug on` from the
+    // UCI thread takes effect without waiting for the search to release the engine.
+    debug: Arc<AtomicBool>,
+    // NullMovePruning option, off to rule it out when chasing a tactical miss
+    null_move: bool,
+    // UCI_AnalyseMode: the GUI is analysing, so no pruning on a guess (null move, futility)
+    analyse_mode: bool,
+    // Ponder option: bestmove names the expected reply only when the GUI allows pondering
+    ponder: bool,
+    // PreferShortMates option: a mate found at a shallower depth stands against longer ones
+    prefer_short_mates: bool,
+    multi_pv: usize,
+    max_depth: u32,
+    slow_mover: u64,
+    // EvalNoise: bound in centipawns on the random offset given to each root move
+    eval_noise: i32,
+    // Drawn once per search so a move keeps the same offset across iterations
+    root_noise: Vec<(Move, i32)>,
+    search_moves: Vec<Move>,
+    // Root moves already reported as a better MultiPV line in the current iteration
+    root_excluded: Vec<Move>,
+    root_lines: Vec<RootLine>,
+    // Hashes of the game before the root (oldest first), then of each position on the search path
+    game_history: Vec<u64>,
+    path: Vec<u64>,
+    // Triangular PV table: pv_table[ply] is the best line found from the node at that ply
+    pv_table: Vec<Vec<Move>>,
+    // Repetition draws scored so far; a node whose subtree added one has a path-dependent score
+    repetition_draws: u64,
+    nodes: u64,
+    search_start: Instant,
+    last_info_time: Instant,
+    current_depth: u32,
+    current_root_move: Option<(Move, usize)>,
+    // Set from the UCI thread; polled every 1024 nodes
+    stop: Arc<AtomicBool>,
+    stopped: bool,
+    // Set by the UCI thread for `go ponder` and cleared by `ponderhit`: until then the search runs
+    // without a deadline, and the clock budget is only started once the move is actually played
+    pondering: Arc<AtomicBool>,
+    awaiting_ponderhit: bool,
+    ponder_budget: Option<u64>,
+    node_limit: u64,
+    deadline: Option<Instant>,
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Engine {
     pub fn new() -> Self {
         const EMPTY_KILLERS: [Option<Move>; 2] = [None, None];
         Self {
-            tt: HashMap::with_capacity(2048 * 1024),
-            killers: [EMPTY_KILLERS; 64],
+            tt: TranspositionTable::new(DEFAULT_HASH_MB),
+            hash_mb: DEFAULT_HASH_MB,
+            threads: 1,
+            killers: [EMPTY_KILLERS; MAX_DEPTH as usize],
             history: [[[0; 64]; 64]; 2],
             book: OpeningBook::new(),
+            book_depth: DEFAULT_BOOK_DEPTH,
+            rng: Rng::from_time(),
+            eval_params: EvalParams::default(),
+            contempt: DEFAULT_CONTEMPT,
+            draw_score: None,
+            search_params: SearchParams::default(),
+            pv_notation: PvNotation::Uci,
+            qsearch_mode: QSearchMode::Full,
+            score_perspective: ScorePerspective::SideToMove,
+            debug: Arc::new(AtomicBool::new(false)),
+            null_move: true,
+            analyse_mode: false,
+            ponder: false,
+            prefer_short_mates: true,
+            multi_pv: 1,
+            max_depth: MAX_DEPTH,
+            slow_mover: DEFAULT_SLOW_MOVER,
+            eval_noise: 0,
+            root_noise: Vec::new(),
+            search_moves: Vec::new(),
+            root_excluded: Vec::new(),
+            root_lines: Vec::new(),
+            game_history: Vec::new(),
+            path: Vec::new(),
+            pv_table: Vec::new(),
+            repetition_draws: 0,
+            nodes: 0,
+            search_start: Instant::now(),
+            last_info_time: Instant::now(),
+            current_depth: 0,
+            current_root_move: None,
+            stop: Arc::new(AtomicBool::new(false)),
+            stopped: false,
+            pondering: Arc::new(AtomicBool::new(false)),
+            awaiting_ponderhit: false,
+            ponder_budget: None,
+            node_limit: u64::MAX,
+            deadline: None,
         }
     }
 
-    fn see_simple(&self, m: &Move, pos: &Chess) -> i32 {
-        let victim = pos.board().piece_at(m.to()).map(|p| p.role).unwrap_or(Role::Pawn);
-        let attacker = pos.board().piece_at(m.from().unwrap()).map(|p| p.role).unwrap_or(Role::Pawn);
-        get_material_value(victim) - get_material_value(attacker) / 10
+    // Start of a new game: forget everything learned from earlier searches but keep the
+    // options set through setoption (hash size, contempt, eval params, book, seed...)
+    pub fn reset(&mut self) {
+        self.tt.clear();
+        self.killers = [const { [None, None] }; MAX_DEPTH as usize];
+        self.history = [[[0; 64]; 64]; 2];
+        self.root_lines.clear();
+        self.game_history.clear();
+        self.current_depth = 0;
+        self.current_root_move = None;
     }
 
-    fn order_moves(&self, pos: &Chess, moves: &mut MoveList, hash_move: Option<&Move>, depth: u32) {
-        let turn_idx = if pos.turn() == Color::White { 0 } else { 1 };
-        moves.sort_by_cached_key(|m| {
-            if let Some(hm) = hash_move {
-                if m == hm { return -4000000; }
+    // Positions played before the one being searched, oldest first, for repetition detection
+    pub fn set_game_history(&mut self, hashes: Vec<u64>) {
+        self.game_history = hashes;
+    }
+
+    // The same history kept move by move, for embedders running a game without UCI strings:
+    // push the full_hash of each position as a move is played from it, pop on takeback, and
+    // pass the position after the last move to find_best_move. `reset` starts it afresh.
+    // The UCI loop replays `position` commands itself and hands over the lot with set_game_history.
+    pub fn push_position(&mut self, hash: u64) {
+        self.game_history.push(hash);
+    }
+
+    pub fn pop_position(&mut self) -> Option<u64> {
+        self.game_history.pop()
+    }
+
+    // At least one, and no more than the machine has cores
+    pub fn set_threads(&mut self, threads: usize) {
+        let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+        self.threads = threads.clamp(1, cores.min(MAX_THREADS));
+    }
+
+    pub fn set_hash_size(&mut self, mb: usize) {
+        self.hash_mb = mb.clamp(1, MAX_HASH_MB);
+        self.tt = TranspositionTable::new(self.hash_mb);
+    }
+
+    // Same seed and limits give the same moves, for reproducible games and tests
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = Rng::new(seed);
+        self.book.set_rng(Rng::new(self.rng.next_u64()));
+    }
+
+    pub fn set_book_temperature(&mut self, temperature: f64) {
+        self.book.set_temperature(temperature);
+    }
+
+    pub fn set_book_depth(&mut self, plies: u32) {
+        self.book_depth = plies;
+    }
+
+    pub fn set_eval_params(&mut self, params: EvalParams) {
+        let pst = std::mem::take(&mut self.eval_params.pst);
+        self.eval_params = EvalParams { mode: self.eval_params.mode, kind: self.eval_params.kind, piece_values: self.eval_params.piece_values, pst, ..params };
+    }
+
+    pub fn set_piece_values(&mut self, values: [i32; 5]) {
+        self.eval_params.piece_values = values;
+    }
+
+    pub fn set_pst(&mut self, pst: PstSet) {
+        self.eval_params.pst = pst;
+    }
+
+    pub fn set_eval_mode(&mut self, mode: EvalMode) {
+        self.eval_params.mode = mode;
+    }
+
+    pub fn set_eval_kind(&mut self, kind: EvalKind) {
+        self.eval_params.kind = kind;
+    }
+
+    pub fn nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    // Table traffic during the last search
+    pub fn tt_stats(&self) -> TTStats {
+        self.tt.stats()
+    }
+
+    // One line describing the last search, for the LogFile
+    pub fn search_summary(&self) -> String {
+        let score = self.root_lines.first().map_or("none".to_string(), |line| format_score(line.score));
+        format!("depth {} score {} nodes {} time {}", self.current_depth, score, self.nodes, self.search_start.elapsed().as_millis())
+    }
+
+    pub fn set_score_perspective(&mut self, perspective: ScorePerspective) {
+        self.score_perspective = perspective;
+    }
+
+    pub fn set_pv_notation(&mut self, notation: PvNotation) {
+        self.pv_notation = notation;
+    }
+
+    pub fn set_qsearch_mode(&mut self, mode: QSearchMode) {
+        self.qsearch_mode = mode;
+    }
+
+    pub fn set_capture_ordering(&mut self, ordering: CaptureOrdering) {
+        self.search_params.capture_ordering = ordering;
+    }
+
+    pub fn set_multi_pv(&mut self, lines: usize) {
+        self.multi_pv = lines.max(1);
+    }
+
+    pub fn set_slow_mover(&mut self, percent: u64) {
+        self.slow_mover = percent.clamp(10, MAX_SLOW_MOVER);
+    }
+
+    pub fn set_max_depth(&mut self, depth: u32) {
+        self.max_depth = depth.clamp(1, MAX_DEPTH);
+    }
+
+    pub fn set_eval_noise(&mut self, centipawns: i32) {
+        self.eval_noise = centipawns.max(0);
+    }
+
+    pub fn set_null_move(&mut self, enabled: bool) {
+        self.null_move = enabled;
+    }
+
+    pub fn set_analyse_mode(&mut self, enabled: bool) {
+        self.analyse_mode = enabled;
+    }
+
+    pub fn set_ponder(&mut self, ponder: bool) {
+        self.ponder = ponder;
+    }
+
+    pub fn set_prefer_short_mates(&mut self, enabled: bool) {
+        self.prefer_short_mates = enabled;
+    }
+
+    // The reply the PV expects to `best`, for `bestmove ... ponder`. None with Ponder off, or
+    // when `best` didn't come from the last search's PV (book and forced moves).
+    pub fn ponder_move(&self, best: &Move) -> Option<Move> {
+        if !self.ponder { return None; }
+        let pv = &self.root_lines.first()?.pv;
+        if pv.first() != Some(best) { return None; }
+        pv.get(1).cloned()
+    }
+
+    pub fn set_debug(&mut self, debug: bool) {
+        self.debug.store(debug, Ordering::Relaxed);
+    }
+
+    pub fn debug_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.debug)
+    }
+
+    fn debug(&self) -> bool {
+        self.debug.load(Ordering::Relaxed)
+    }
+
+    pub fn pondering_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.pondering)
+    }
+
+    pub fn stop_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.stop)
+    }
+
+    pub fn set_contempt(&mut self, contempt: i32) {
+        self.contempt = contempt;
+    }
+
+    pub fn set_draw_score(&mut self, score: i32) {
+        self.draw_score = Some(score);
+    }
+
+    // Draws are scored from the root side's point of view, so the sign flips every ply.
+    // Contempt is a reluctance (a draw is worth -contempt to us); UCI_DrawScore states the value
+    // directly and wins over contempt rather than adding to it.
+    fn draw_score(&self, ply: u32) -> i32 {
+        let root_value = self.draw_score.unwrap_or(-self.contempt);
+        if ply.is_multiple_of(2) { root_value } else { -root_value }
+    }
+
+    // Wide at shallow depths where scores are noisy, tightening toward the aspiration delta
+    fn aspiration_window(&self, depth: u32, swing: i32) -> i32 {
+        let delta = self.search_params.aspiration_delta;
+        (delta + 2 * delta / depth as i32).max(swing)
+    }
+
+    // Window for the next iteration, widened when the score is still swinging. A mate score
+    // gets the full window: the next iteration is looking for a shorter mate, and a band of
+    // ±delta around it would only fail and force a re-search.
+    fn root_window(&self, depth: u32, score: i32, prev_score: Option<i32>) -> (i32, i32) {
+        if score.abs() >= 29000 { return (-40000, 40000); }
+        let swing = prev_score.map_or(0, |prev| (score - prev).abs());
+        let delta = self.aspiration_window(depth, swing);
+        (score - delta, score + delta)
+    }
+
+    fn root_move_allowed(&self, m: &Move) -> bool {
+        (self.search_moves.is_empty() || self.search_moves.contains(m)) && !self.root_excluded.contains(m)
+    }
+
+    // A single repetition inside the search is already a draw: the side that could avoid it
+    // would have, so the line is as good as repeated three times.
+    fn is_repetition(&self, hash: u64, halfmoves: u32) -> bool {
+        repetitions(&self.path, hash, halfmoves) > 0
+    }
+
+    // Node and time limits wait for depth 1 to finish, however small the budget: it takes a few
+    // milliseconds and guarantees a searched move. Only the stop command cuts it short.
+    fn poll_stop(&mut self) -> bool {
+        if self.nodes & 1023 == 0 {
+            self.maybe_emit_info();
+            if self.awaiting_ponderhit && !self.pondering.load(Ordering::Relaxed) {
+                self.awaiting_ponderhit = false;
+                self.deadline = self.ponder_budget.map(|ms| Instant::now() + Duration::from_millis(ms));
             }
-            if m.is_capture() {
-                return -2000000 - self.see_simple(m, pos);
+            let limits_apply = self.current_depth > 1;
+            if self.stop.load(Ordering::Relaxed)
+                || (limits_apply && self.nodes >= self.node_limit)
+                || (limits_apply && self.deadline.is_some_and(|deadline| Instant::now() >= deadline)) {
+                self.stopped = true;
             }
-            
-            if depth < 64 {
-                if Some(m.clone()) == self.killers[depth as usize][0] { return -900000; }
-                if Some(m.clone()) == self.killers[depth as usize][1] { return -800000; }
+        }
+        self.stopped
+    }
+
+    fn nps(&self) -> (u64, u64) {
+        let ms = self.search_start.elapsed().as_millis() as u64;
+        (ms, self.nodes * 1000 / ms.max(1))
+    }
+
+    fn maybe_emit_info(&mut self) {
+        let now = Instant::now();
+        if now.duration_since(self.last_info_time) < INFO_INTERVAL { return; }
+        self.last_info_time = now;
+        let (ms, nps) = self.nps();
+        let mut line = format!("info depth {}", self.current_depth);
+        if let Some((ref m, number)) = self.current_root_move {
+            line.push_str(&format!(" currmove {} currmovenumber {}", m.to_uci(CastlingMode::Standard), number));
+        }
+        uci_log::send(&format!("{} nodes {} nps {} time {}", line, self.nodes, nps, ms));
+    }
+
+    fn emit_depth_info(&mut self, pos: &Chess, depth: u32, multi_pv: bool) {
+        self.last_info_time = Instant::now();
+        let (ms, nps) = self.nps();
+        let sign = if self.score_perspective == ScorePerspective::White && pos.turn() == Color::Black { -1 } else { 1 };
+        for (k, line) in self.root_lines.iter().enumerate() {
+            let mut out = format!("info depth {}", depth);
+            if multi_pv {
+                out.push_str(&format!(" multipv {}", k + 1));
             }
+            out.push_str(&format!(" score {} nodes {} nps {} time {} pv {}", format_score(sign * line.score), self.nodes, nps, ms, format_pv(pos, &line.pv, self.pv_notation)));
+            uci_log::send(&out);
+        }
+    }
 
-            if let (Some(from), to) = (m.from(), m.to()) {
-                let h_score = self.history[turn_idx][from as usize][to as usize];
-                return -(h_score as i32);
+    // Rank of a capture within the captures, higher first
+    fn capture_score(&self, pos: &Chess, m: &Move) -> i32 {
+        match self.search_params.capture_ordering {
+            CaptureOrdering::MvvLva => {
+                let victim = m.capture().map_or(0, |role| self.eval_params.material_value(role));
+                victim * 10 - self.eval_params.material_value(m.role())
             }
+            CaptureOrdering::See => see(pos, m, &self.eval_params),
+        }
+    }
 
-            if m.is_promotion() { return -700000; }
-            0
-        });
+    // Tooling entry point: nothing in the search or the UCI loop asks for this
+    pub fn evaluate_move(&self, pos: &Chess, m: &Move) -> MoveAssessment {
+        let sign = if pos.turn() == Color::White { 1 } else { -1 };
+        let mut next_pos = pos.clone();
+        next_pos.play_unchecked(m);
+        MoveAssessment {
+            see: sign * see(pos, m, &self.eval_params),
+            eval_after: -sign * evaluate(&next_pos, &self.eval_params),
+        }
     }
 
-    fn quiescence(&self, pos: &Chess, mut alpha: i32, beta: i32) -> i32 {
-        let stand_pat = evaluate(pos);
+    fn move_order_key(&self, pos: &Chess, m: &Move, hash_move: Option<&Move>, depth: u32, turn_idx: usize) -> i32 {
+        if hash_move == Some(m) { return -4000000; }
+        let promotion_gain = m.promotion().map_or(0, |role| self.eval_params.material_value(role) - self.eval_params.material_value(Role::Pawn));
+        if m.is_capture() {
+            return -2000000 - self.capture_score(pos, m) - promotion_gain;
+        }
+        // A quiet queen promotion wins material just like a good capture
+        if m.promotion() == Some(Role::Queen) {
+            return -2000000 - promotion_gain;
+        }
+
+        if depth < MAX_DEPTH {
+            if self.killers[depth as usize][0].as_ref() == Some(m) { return -900000; }
+            if self.killers[depth as usize][1].as_ref() == Some(m) { return -800000; }
+        }
+
+        if let (Some(from), to) = (m.from(), m.to()) {
+            let h_score = self.history[turn_idx][from as usize][to as usize];
+            return -h_score;
+        }
+
+        if m.is_promotion() { return -700000; }
+        0
+    }
+
+    // Plies a late quiet move is searched shallower by, given its place in the list and its history
+    fn late_move_reduction(&self, turn: Color, m: &Move, move_index: usize, depth: u32) -> u32 {
+        let turn_idx = if turn == Color::White { 0 } else { 1 };
+        let history = m.from().map_or(0, |from| self.history[turn_idx][from as usize][m.to() as usize]);
+        self.search_params.lmr_reduction(move_index, depth, history)
+    }
+
+    // A quiet cutoff earns `m` a bonus of depth^2 (capped) and every quiet searched before it
+    // the same malus. Gravity keeps entries within ±HISTORY_MAX: the nearer an entry is to the
+    // bound, the less it moves, so old results fade instead of saturating the table.
+    fn update_history(&mut self, turn: Color, m: &Move, tried: &[Move], depth: u32) {
+        let turn_idx = if turn == Color::White { 0 } else { 1 };
+        let bonus = (depth * depth).min(HISTORY_BONUS_MAX as u32) as i32;
+        let mut apply = |m: &Move, bonus: i32| {
+            if let Some(from) = m.from() {
+                let entry = &mut self.history[turn_idx][from as usize][m.to() as usize];
+                *entry += bonus - *entry * bonus.abs() / HISTORY_MAX;
+            }
+        };
+        apply(m, bonus);
+        for quiet in tried.iter().filter(|q| !q.is_capture()) {
+            apply(quiet, -bonus);
+        }
+    }
+
+    fn order_moves(&self, pos: &Chess, moves: &mut MoveList, hash_move: Option<&Move>, depth: u32) {
+        let turn_idx = if pos.turn() == Color::White { 0 } else { 1 };
+        let mut keys = [0i32; 256];
+        for (i, m) in moves.iter().enumerate() {
+            keys[i] = self.move_order_key(pos, m, hash_move, depth, turn_idx);
+        }
+
+        // Stable insertion sort on the stack: no per-node allocation and quick for typical move counts
+        for i in 1..moves.len() {
+            let mut j = i;
+            while j > 0 && keys[j - 1] > keys[j] {
+                keys.swap(j - 1, j);
+                moves.swap(j - 1, j);
+                j -= 1;
+            }
+        }
+    }
+
+    fn quiescence(&mut self, pos: &Chess, mut alpha: i32, beta: i32) -> i32 {
+        self.nodes += 1;
+        if self.poll_stop() { return 0; }
+        let stand_pat = evaluate(pos, &self.eval_params);
         if stand_pat >= beta { return beta; }
         if alpha < stand_pat { alpha = stand_pat; }
 
-        let mut captures = pos.legal_moves();
-        captures.retain(|m| m.is_capture());
-        self.order_moves(pos, &mut captures, None, 0);
+        let mut tactical = tactical_moves(pos);
+        if self.qsearch_mode == QSearchMode::Shallow {
+            let params = &self.eval_params;
+            let min_gain = self.search_params.qsearch_shallow_min_gain;
+            tactical.retain(|m| {
+                let gain = m.capture().map_or(0, |role| params.material_value(role))
+                    + m.promotion().map_or(0, |role| params.material_value(role) - params.material_value(Role::Pawn));
+                gain >= min_gain
+            });
+        }
+        self.order_moves(pos, &mut tactical, None, 0);
 
-        for m in captures {
+        for m in tactical {
             let mut next_pos = pos.clone();
             next_pos.play_unchecked(&m);
+            // Knight underpromotions are only worth a look when they check (forks); otherwise the queen is better
+            if m.promotion() == Some(Role::Knight) && !next_pos.is_check() { continue; }
             let score = -self.quiescence(&next_pos, -beta, -alpha);
             
             if score >= beta { return beta; }
@@ -75,102 +638,152 @@ impl Engine {
         alpha
     }
 
-    pub fn alpha_beta(&mut self, pos: &Chess, mut alpha: i32, mut beta: i32, mut depth: u32, ply: u32) -> i32 {
-        let hash = pos.zobrist_hash::<Zobrist64>(shakmaty::EnPassantMode::Always).0;
+    #[allow(clippy::too_many_arguments)]
+    pub fn alpha_beta(&mut self, pos: &Chess, hash: u64, mut alpha: i32, mut beta: i32, mut depth: u32, ply: u32, is_pv: bool) -> i32 {
+        // Once stopped, every result is garbage: unwind without touching the TT
+        self.nodes += 1;
+        if self.poll_stop() { return 0; }
+
+        // Every early return leaves an empty line; the move loop fills it in
+        let ply_idx = ply as usize;
+        if self.pv_table.len() <= ply_idx + 1 {
+            self.pv_table.resize(ply_idx + 2, Vec::new());
+        }
+        self.pv_table[ply_idx].clear();
+
+        if ply > 0 && self.is_repetition(hash, pos.halfmoves()) {
+            self.repetition_draws += 1;
+            return self.draw_score(ply);
+        }
+        let repetitions_before = self.repetition_draws;
+
         let is_check = pos.is_check();
 
         if is_check { depth += 1; }
 
-        if let Some(entry) = self.tt.get(&hash) {
-            if entry.depth >= depth {
-                match entry.node_type {
-                    NodeType::Exact => return entry.score,
-                    NodeType::LowerBound => alpha = alpha.max(entry.score),
-                    NodeType::UpperBound => beta = beta.min(entry.score),
-                }
-                if alpha >= beta { return entry.score; }
+        // No cutoffs at the root: the stored score may be for a different searchmoves/MultiPV restriction.
+        // Path-dependent scores only lend their move (see TTEntry::path_dependent). Nor in PV nodes,
+        // where a cutoff would leave the PV table without the rest of the line.
+        if let Some(entry) = self.tt.probe(hash).filter(|e| ply > 0 && !is_pv && e.depth >= depth && !e.path_dependent) {
+            match entry.node_type {
+                NodeType::Exact => return entry.score,
+                NodeType::LowerBound => alpha = alpha.max(entry.score),
+                NodeType::UpperBound => beta = beta.min(entry.score),
             }
+            if alpha >= beta { return entry.score; }
         }
 
+        // Generated once and reused for the game-over test and the move loop
+        let mut legals = pos.legal_moves();
+        if legals.is_empty() {
+            if is_check { return -30000 + ply as i32; }
+            return self.draw_score(ply);
+        }
+        if pos.is_insufficient_material() { return self.draw_score(ply); }
         if depth == 0 { return self.quiescence(pos, alpha, beta); }
-        if pos.is_game_over() { return evaluate(pos); }
 
-        if depth == 1 && !is_check {
-            let static_eval = evaluate(pos);
-            if static_eval - 160 >= beta { return beta; }
+        // Pruning on a guess is for zero-window nodes; a PV node owes an exact score
+        let may_prune = !is_pv && !self.analyse_mode;
+        if depth == 1 && !is_check && may_prune {
+            let static_eval = evaluate(pos, &self.eval_params);
+            if static_eval - self.search_params.futility_margin >= beta { return beta; }
         }
 
-        if depth >= 3 && !is_check && ply > 0 {
-            let board = pos.board();
-            let major_pieces = if pos.turn() == Color::White {
-                (board.white() & !board.pawns() & !board.kings()).any()
-            } else {
-                (board.black() & !board.pawns() & !board.kings()).any()
-            };
-
-            if major_pieces {
-                if let Ok(next_pos) = pos.clone().swap_turn() {
-                    let score = -self.alpha_beta(&next_pos, -beta, -(beta - 1), depth - 3, ply + 1);
-                    if score >= beta { return beta; }
-                }
-            }
+        if self.null_move && may_prune && depth >= self.search_params.null_move_min_depth && !is_check && ply > 0
+            && let Some(next_pos) = null_move_position(pos) {
+            let next_hash = hash_after_null(pos, hash, &next_pos);
+            self.path.push(hash);
+            let score = -self.alpha_beta(&next_pos, next_hash, -beta, -(beta - 1), depth.saturating_sub(self.search_params.null_move_reduction), ply + 1, false);
+            self.path.pop();
+            if self.stopped { return 0; }
+            if score >= beta { return beta; }
         }
 
-        let mut hash_move = self.tt.get(&hash).and_then(|e| e.best_move.as_ref());
-        if hash_move.is_none() && depth >= 4 {
-            self.alpha_beta(pos, alpha, beta, depth - 2, ply + 1);
-            hash_move = self.tt.get(&hash).and_then(|e| e.best_move.as_ref());
+        // A key collision or stale entry can hand back a move from another position: only a legal one is trusted
+        let mut hash_move = self.tt.probe(hash).and_then(|e| e.best_move);
+        if let Some(m) = hash_move.take_if(|m| !legals.contains(m)) && self.debug() {
+            info_string(&format!("tt collision at {:016x}: stored move {} is illegal", hash, m.to_uci(CastlingMode::Standard)));
+        }
+        if hash_move.is_none() && depth >= self.search_params.iid_min_depth {
+            self.alpha_beta(pos, hash, alpha, beta, depth.saturating_sub(self.search_params.iid_reduction), ply, is_pv);
+            if self.stopped { return 0; }
+            hash_move = self.tt.probe(hash).and_then(|e| e.best_move).filter(|m| legals.contains(m));
         }
 
-        let mut legals = pos.legal_moves();
-        if legals.is_empty() {
-            if is_check { return -30000 + ply as i32; }
-            return 0;
+        if ply == 0 {
+            legals.retain(|m| self.root_move_allowed(m));
         }
 
-        self.order_moves(pos, &mut legals, hash_move, depth);
+        self.order_moves(pos, &mut legals, hash_move.as_ref(), depth);
 
         let mut best_move_found = None;
         let mut best_score = -40000;
+        let mut best_ranked = -40000;
         let old_alpha = alpha;
+        // IID above may have written a line for this ply
+        self.pv_table[ply_idx].clear();
 
         for (i, m) in legals.iter().enumerate() {
+            if ply == 0 {
+                self.current_root_move = Some((m.clone(), i + 1));
+                self.maybe_emit_info();
+            }
+
+            // shakmaty has no unmake; Chess is a small heap-free struct, so copy-make is cheap
             let mut next_pos = pos.clone();
             next_pos.play_unchecked(m);
-            
+            let next_hash = hash_after(pos, hash, m, &next_pos);
+
+            self.path.push(hash);
             let mut score;
             if i == 0 {
-                score = -self.alpha_beta(&next_pos, -beta, -alpha, depth - 1, ply + 1);
+                score = -self.alpha_beta(&next_pos, next_hash, -beta, -alpha, depth - 1, ply + 1, is_pv);
             } else {
-                if i >= 4 && depth >= 3 && !m.is_capture() && !is_check && !next_pos.is_check() {
-                    let reduction = 1 + (i as u32 / 4).min(depth / 3);
-                    score = -self.alpha_beta(&next_pos, -(alpha + 1), -alpha, depth - 1 - reduction, ply + 1);
+                if self.search_params.lmr_applies(i, depth, is_pv) && !m.is_capture() && !is_check && !next_pos.is_check() {
+                    let reduction = self.late_move_reduction(pos.turn(), m, i, depth);
+                    score = -self.alpha_beta(&next_pos, next_hash, -(alpha + 1), -alpha, (depth - 1).saturating_sub(reduction), ply + 1, false);
                 } else {
                     score = alpha + 1;
                 }
 
                 if score > alpha {
-                    score = -self.alpha_beta(&next_pos, -(alpha + 1), -alpha, depth - 1, ply + 1);
+                    score = -self.alpha_beta(&next_pos, next_hash, -(alpha + 1), -alpha, depth - 1, ply + 1, false);
                     if score > alpha && score < beta {
-                        score = -self.alpha_beta(&next_pos, -beta, -alpha, depth - 1, ply + 1);
+                        score = -self.alpha_beta(&next_pos, next_hash, -beta, -alpha, depth - 1, ply + 1, is_pv);
                     }
                 }
             }
-            
-            if score > best_score {
-                best_score = score;
+            self.path.pop();
+            if self.stopped { return 0; }
+            // Root noise only picks which root move is played. The window, the cutoff and the score
+            // returned and stored all stay on searched scores, and a mate is never blurred into a
+            // different distance.
+            let mut ranked = score;
+            if ply == 0 && score.abs() < 29000 {
+                ranked += self.root_noise.iter().find(|(rm, _)| rm == m).map_or(0, |(_, noise)| *noise);
+            }
+            best_score = best_score.max(score);
+            let picked = ranked > best_ranked;
+            if picked {
+                best_ranked = ranked;
                 best_move_found = Some(m.clone());
             }
 
+            // The root line follows the move to be played, which under noise need not raise alpha
+            let extends_line = if ply == 0 { picked } else { score > alpha };
+            if extends_line {
+                let (parent, child) = self.pv_table.split_at_mut(ply_idx + 1);
+                let line = &mut parent[ply_idx];
+                line.clear();
+                line.push(m.clone());
+                line.extend_from_slice(&child[0]);
+            }
             alpha = alpha.max(score);
             if alpha >= beta {
-                if !m.is_capture() && depth < 64 {
+                if !m.is_capture() && depth < MAX_DEPTH {
                     self.killers[depth as usize][1] = self.killers[depth as usize][0].clone();
                     self.killers[depth as usize][0] = Some(m.clone());
-                    let turn_idx = if pos.turn() == Color::White { 0 } else { 1 };
-                    if let (Some(from), to) = (m.from(), m.to()) {
-                        self.history[turn_idx][from as usize][to as usize] += depth * depth;
-                    }
+                    self.update_history(pos.turn(), m, &legals[..i], depth);
                 }
                 break;
             }
@@ -180,42 +793,1129 @@ impl Engine {
                         else if best_score >= beta { NodeType::LowerBound }
                         else { NodeType::Exact };
 
-        self.tt.insert(hash, TTEntry { depth, score: best_score, node_type, best_move: best_move_found });
+        let path_dependent = self.repetition_draws != repetitions_before;
+        self.tt.store(hash, &TTEntry { depth, score: best_score, node_type, best_move: best_move_found, path_dependent });
         best_score
     }
 
-    pub fn find_best_move(&mut self, pos: &Chess, max_depth: u32) -> Option<Move> {
-        if let Some(m_str) = self.book.get_move(pos) {
-            if let Ok(uci_move) = m_str.parse::<shakmaty::uci::UciMove>() {
-                if let Ok(m) = uci_move.to_move(pos) {
-                    return Some(m);
-                }
+    pub fn find_best_move(&mut self, pos: &Chess, limits: &SearchLimits) -> Option<Move> {
+        // While pondering, bestmove has to wait for ponderhit or stop just as in infinite mode
+        let open_ended = limits.infinite || self.pondering.load(Ordering::Relaxed);
+        debug_assert!(evaluate_is_symmetric(pos, &self.eval_params), "asymmetric evaluation in {}",
+            shakmaty::fen::Fen::from_position(pos.clone(), shakmaty::EnPassantMode::Legal));
+
+        // Analysis wants the engine's own opinion, not a book move. The game history holds one
+        // hash per ply played, which gives how deep into the game we are for BookDepth.
+        let in_book_depth = self.game_history.len() < self.book_depth as usize;
+        if limits.search_moves.is_empty() && !open_ended && in_book_depth
+            && let Some(m_str) = self.book.get_move(pos) {
+            if let Ok(uci_move) = m_str.parse::<shakmaty::uci::UciMove>() && let Ok(m) = uci_move.to_move(pos) {
+                info_string(&format!("book move {}", m_str));
+                return Some(m);
             }
+            info_string(&format!("book move {} is not legal here, searching", m_str));
+        }
+
+        // A forced reply needs no search, and under a clock the time is better kept
+        let legal = pos.legal_moves();
+        if legal.len() == 1 && !open_ended {
+            self.nodes = 0;
+            self.current_depth = 0;
+            self.root_lines.clear();
+            self.search_start = Instant::now();
+            uci_log::send(&format!("info depth 0 nodes 0 time 0 pv {}", format_pv(pos, &legal[..], self.pv_notation)));
+            return Some(legal[0].clone());
         }
 
-        let mut overall_best_move = None;
+        if self.threads > 1 && self.debug() {
+            info_string(&format!("Threads {} requested, searching on one thread", self.threads));
+        }
+
+        let mut overall_best: Option<RootLine> = None;
         let mut alpha = -40000;
         let mut beta = 40000;
+        let mut prev_score = None;
+
+        let root_hash = full_hash(pos);
+        self.nodes = 0;
+        self.stopped = false;
+        self.path = self.game_history.clone();
+        self.node_limit = limits.nodes.unwrap_or(u64::MAX);
+        self.search_start = Instant::now();
+        self.last_info_time = self.search_start;
+        self.ponder_budget = limits.allocate_time(pos.turn(), self.slow_mover);
+        self.awaiting_ponderhit = self.pondering.load(Ordering::Relaxed);
+        self.deadline = self.ponder_budget.filter(|_| !self.awaiting_ponderhit).map(|ms| self.search_start + Duration::from_millis(ms));
+        self.tt.reset_stats();
 
-        for depth in 1..=max_depth {
-            let score = self.alpha_beta(pos, alpha, beta, depth, 0);
+        // MultiPV lines are drawn only from the searchmoves set, when one is given
+        self.search_moves = limits.search_moves.iter().filter(|m| pos.is_legal(m)).cloned().collect();
+        self.root_excluded.clear();
+        let root_moves = pos.legal_moves().iter().filter(|m| self.root_move_allowed(m)).count();
+
+        // Variety without touching the tree below the root: each root move gets a fixed offset
+        // in [-noise, noise] from the master PRNG, so a seeded engine stays reproducible
+        self.root_noise.clear();
+        if self.eval_noise > 0 {
+            let span = 2 * self.eval_noise as u64 + 1;
+            for m in pos.legal_moves() {
+                let noise = (self.rng.next_u64() % span) as i32 - self.eval_noise;
+                self.root_noise.push((m, noise));
+            }
+        }
+        let lines = self.multi_pv.min(root_moves).max(1);
+
+        for depth in 1..=limits.depth.min(self.max_depth) {
+            self.current_depth = depth;
+            self.root_excluded.clear();
+            self.root_lines.clear();
+
+            for k in 0..lines {
+                self.current_root_move = None;
+                let score = if k == 0 {
+                    let mut score = self.alpha_beta(pos, root_hash, alpha, beta, depth, 0, true);
+                    if score <= alpha || score >= beta {
+                        if self.debug() {
+                            info_string(&format!("depth {} score {} outside window [{}, {}], re-searching", depth, score, alpha, beta));
+                        }
+                        score = self.alpha_beta(pos, root_hash, -40000, 40000, depth, 0, true);
+                    }
 
-            if score <= alpha || score >= beta {
-                alpha = -40000;
-                beta = 40000;
-                let _ = self.alpha_beta(pos, alpha, beta, depth, 0);
+                    (alpha, beta) = self.root_window(depth + 1, score, prev_score);
+                    prev_score = Some(score);
+                    score
+                } else {
+                    self.alpha_beta(pos, root_hash, -40000, 40000, depth, 0, true)
+                };
+
+                // An interrupted iteration is discarded; the previous depth's move stands
+                if self.stopped { break; }
+
+                let pv = self.pv_table[0].clone();
+                let Some(first) = pv.first() else { break; };
+                self.root_excluded.push(first.clone());
+                self.root_lines.push(RootLine { score, pv });
+            }
+            self.root_excluded.clear();
+            if self.stopped { break; }
+            self.root_lines.sort_by_key(|line| -line.score);
+
+            if let Some(best) = overall_best.take() {
+                self.restore_faster_mate(best, lines);
+            }
+            overall_best = self.root_lines.first().cloned();
+            self.emit_depth_info(pos, depth, lines > 1);
+            if self.debug() {
+                info_string(&format!("depth {} tt {} permille of {} entries", depth, self.tt.hashfull(), self.tt.capacity()));
+            }
+
+            // A reduced late move can hide a shorter quiet mate, so a mate only ends the search
+            // once it would still fit inside the horizon after the deepest reduction
+            if lines == 1 && self.root_lines.first().is_some_and(|line| line.score >= 29000
+                && (30000 - line.score) as u32 + self.search_params.max_reduction(depth) <= depth) {
+                break;
+            }
+        }
+        if self.debug() {
+            let stats = self.tt_stats();
+            info_string(&format!("tt probes {} hits {} stores {} collisions {} full {} permille",
+                stats.probes, stats.hits, stats.stores, stats.collisions, stats.occupancy));
+        }
+
+        // Ran out of depth in infinite mode: the GUI still expects bestmove only after stop, or
+        // after ponderhit when pondering
+        while (limits.infinite || self.pondering.load(Ordering::Relaxed)) && !self.stop.load(Ordering::Relaxed) {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+
+        // Stopped by the GUI before depth 1 finished: any allowed move beats no move
+        overall_best.map(|line| line.pv[0].clone()).or_else(|| pos.legal_moves().into_iter().find(|m| self.root_move_allowed(m)))
+    }
+
+    // With PreferShortMates, the previous iteration's mate beats a longer one found now. Its line
+    // goes back on top, so the info, the summary and the ponder move describe the move played.
+    fn restore_faster_mate(&mut self, best: RootLine, lines: usize) {
+        if !self.prefer_short_mates || !self.root_lines.first().is_some_and(|line| keeps_faster_mate(best.score, line.score)) {
+            return;
+        }
+        self.root_lines.retain(|line| line.pv[0] != best.pv[0]);
+        self.root_lines.insert(0, best);
+        self.root_lines.truncate(lines);
+    }
+}
+
+// A higher root score already means a shorter mate, or a longer defence against one. On top of
+// that a win proven at a shallower depth stands until a deeper iteration mates at least as fast,
+// so a TT or window artefact can't trade a mate in 2 for a mate in 4.
+fn keeps_faster_mate(best: i32, score: i32) -> bool {
+    best >= 29000 && score < best
+}
+
+// Earlier occurrences of `hash` in `history` (oldest first, ending with the position before this
+// one) with the same side to move and since the last irreversible move, `halfmoves` plies back
+fn repetitions(history: &[u64], hash: u64, halfmoves: u32) -> usize {
+    let n = history.len();
+    let limit = (halfmoves as usize).min(n);
+    (2..=limit).step_by(2).filter(|&back| history[n - back] == hash).count()
+}
+
+// Draws the arbiter would declare or a player could claim: insufficient material, the fifty-move
+// rule and threefold repetition. `history` holds the full_hash of every earlier position of the
+// game, oldest first. Stalemate is left to game_result, which needs the move list anyway.
+pub fn is_draw_by_rules(pos: &Chess, history: &[u64]) -> bool {
+    pos.is_insufficient_material()
+        || pos.halfmoves() >= 100
+        || repetitions(history, full_hash(pos), pos.halfmoves()) >= 2
+}
+
+// For match drivers: the result once the game is over, None while it goes on. Mate on the move
+// that reaches the fifty-move limit still wins.
+pub fn game_result(pos: &Chess, history: &[u64]) -> Option<Outcome> {
+    if pos.legal_moves().is_empty() {
+        return Some(if pos.is_check() { Outcome::Decisive { winner: !pos.turn() } } else { Outcome::Draw });
+    }
+    is_draw_by_rules(pos, history).then_some(Outcome::Draw)
+}
+
+// The position with the turn passed, when a null move is sound. Never in check (swap_turn would
+// leave the side that just "moved" in check and fails), and not with only pawns, where zugzwang is common.
+fn null_move_position(pos: &Chess) -> Option<Chess> {
+    if pos.is_check() { return None; }
+    let board = pos.board();
+    if (board.by_color(pos.turn()) & !board.pawns() & !board.kings()).is_empty() { return None; }
+    pos.clone().swap_turn().ok()
+}
+
+// Legal captures plus quiet queen and knight promotions, for quiescence. Out of check only the
+// moves onto enemy pieces are generated, so the quiet moves are never built: a capture is legal
+// unless the piece is pinned off its line or the king steps onto an attacked square. Evasions
+// are few, and come from the full generator.
+fn tactical_moves(pos: &Chess) -> MoveList {
+    let is_tactical = |m: &Move| m.is_capture() || matches!(m.promotion(), Some(Role::Queen | Role::Knight));
+    let board = pos.board();
+    let us = pos.turn();
+    let Some(king) = board.king_of(us).filter(|_| !pos.is_check()) else {
+        let mut moves = pos.legal_moves();
+        moves.retain(|m| is_tactical(m));
+        return moves;
+    };
+    let ours = board.by_color(us);
+    let theirs = board.by_color(!us);
+
+    let snipers = theirs & ((attacks::rook_attacks(king, Bitboard(0)) & board.rooks_and_queens())
+        | (attacks::bishop_attacks(king, Bitboard(0)) & board.bishops_and_queens()));
+    let mut pinned = Bitboard(0);
+    for sniper in snipers {
+        let blockers = attacks::between(king, sniper) & board.occupied();
+        if blockers.count() == 1 {
+            pinned |= blockers & ours;
+        }
+    }
+
+    let mut moves = pos.en_passant_moves();
+    for from in ours {
+        let Some(role) = board.role_at(from) else { continue; };
+        for to in board.attacks_from(from) & theirs {
+            let legal = if role == Role::King {
+                board.attacks_to(to, !us, board.occupied() ^ Bitboard::from(king)).is_empty()
             } else {
-                alpha = score - 50;
-                beta = score + 50;
+                !pinned.contains(from) || attacks::aligned(king, from, to)
+            };
+            if !legal { continue; }
+            let capture = board.role_at(to);
+            if role == Role::Pawn && Bitboard::BACKRANKS.contains(to) {
+                for promotion in [Role::Queen, Role::Rook, Role::Bishop, Role::Knight] {
+                    moves.push(Move::Normal { role, from, capture, to, promotion: Some(promotion) });
+                }
+            } else {
+                moves.push(Move::Normal { role, from, capture, to, promotion: None });
             }
+        }
+    }
+    moves.extend(pos.promotion_moves().into_iter().filter(|m| !m.is_capture() && is_tactical(m)));
+    moves
+}
+
+// Static exchange evaluation: the material the side to move ends up with when both sides keep
+// recapturing on the target square with their least valuable attacker, each free to stop when
+// going on would lose. Sliders behind a capturer join in (x-rays); pins are not considered.
+fn see(pos: &Chess, m: &Move, params: &EvalParams) -> i32 {
+    let (Some(from), to) = (m.from(), m.to()) else { return 0; };
+    if m.is_castle() { return 0; }
+    let board = pos.board();
+    let mut occupied = board.occupied() ^ Bitboard::from(from);
+    if m.is_en_passant() {
+        occupied ^= Bitboard::from(shakmaty::Square::from_coords(to.file(), from.rank()));
+    }
+
+    let mut gain = [0i32; 33];
+    let mut on_square = m.promotion().unwrap_or(m.role());
+    gain[0] = m.capture().map_or(0, |role| params.material_value(role))
+        + m.promotion().map_or(0, |role| params.material_value(role) - params.material_value(Role::Pawn));
+    let mut side = !pos.turn();
+    let mut d = 0;
+    loop {
+        let attackers = board.attacks_to(to, side, occupied) & occupied;
+        let Some((sq, role)) = Role::ALL.iter().find_map(|&role| (attackers & board.by_role(role)).first().map(|sq| (sq, role))) else { break; };
+        d += 1;
+        gain[d] = params.material_value(on_square) - gain[d - 1];
+        if (-gain[d - 1]).max(gain[d]) < 0 { break; }
+        occupied ^= Bitboard::from(sq);
+        on_square = role;
+        side = !side;
+    }
+    while d > 0 {
+        gain[d - 1] = -(-gain[d - 1]).max(gain[d]);
+        d -= 1;
+    }
+    gain[0]
+}
+
+// GUIs ignore these but they show up in logs
+pub fn info_string(msg: &str) {
+    uci_log::send(&format!("info string {}", msg));
+}
+
+// UCI is what GUIs parse; SAN is for reading logs
+pub fn format_pv(pos: &Chess, pv: &[Move], notation: PvNotation) -> String {
+    match notation {
+        PvNotation::Uci => pv.iter().map(|m| m.to_uci(CastlingMode::Standard).to_string()).collect::<Vec<_>>().join(" "),
+        PvNotation::San => {
+            let mut pos = pos.clone();
+            pv.iter().map(|m| SanPlus::from_move_and_play_unchecked(&mut pos, m).to_string()).collect::<Vec<_>>().join(" ")
+        }
+    }
+}
+
+fn format_score(score: i32) -> String {
+    if score.abs() >= 29000 {
+        let plies = 30000 - score.abs();
+        let moves = (plies + 1) / 2;
+        format!("mate {}", if score > 0 { moves } else { -moves })
+    } else {
+        format!("cp {}", score)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shakmaty::Square;
+    use shakmaty::fen::Fen;
+
+    fn pos_from_fen(fen: &str) -> Chess {
+        fen.parse::<Fen>().unwrap().into_position(CastlingMode::Standard).unwrap()
+    }
+
+    fn best_uci(engine: &mut Engine, pos: &Chess, depth: u32) -> String {
+        engine.find_best_move(pos, &SearchLimits::depth(depth)).unwrap().to_uci(CastlingMode::Standard).to_string()
+    }
+
+    #[test]
+    fn book_depth_limits_book_moves_to_the_first_plies() {
+        // 1.e4 c5: White's third ply is in the book
+        let mut pos = Chess::default();
+        let mut history = Vec::new();
+        for uci in ["e2e4", "c7c5"] {
+            let m = uci.parse::<shakmaty::uci::UciMove>().unwrap().to_move(&pos).unwrap();
+            history.push(full_hash(&pos));
+            pos.play_unchecked(&m);
+        }
+
+        let mut engine = Engine::new();
+        engine.set_game_history(history.clone());
+        engine.find_best_move(&pos, &SearchLimits::depth(2));
+        assert_eq!(engine.nodes(), 0);
+
+        let mut engine = Engine::new();
+        engine.set_book_depth(2);
+        engine.set_game_history(history);
+        engine.find_best_move(&pos, &SearchLimits::depth(2));
+        assert!(engine.nodes() > 0);
+
+        // The first two plies still come from the book
+        let mut engine = Engine::new();
+        engine.set_book_depth(2);
+        engine.set_game_history(vec![full_hash(&Chess::default())]);
+        let e4 = "e2e4".parse::<shakmaty::uci::UciMove>().unwrap().to_move(&Chess::default()).unwrap();
+        engine.find_best_move(&Chess::default().play(&e4).unwrap(), &SearchLimits::depth(2));
+        assert_eq!(engine.nodes(), 0);
+    }
+
+    #[test]
+    fn contempt_avoids_drawing_capture_after_leaving_book() {
+        // Bxh7 leaves same-coloured bishops: an immediate dead draw
+        let pos = pos_from_fen("2b1k3/7p/8/8/8/3B4/8/6K1 w - - 0 1");
+
+        let mut engine = Engine::new();
+        assert_eq!(best_uci(&mut engine, &pos, 4), "d3h7");
+
+        let mut engine = Engine::new();
+        engine.set_contempt(100);
+        assert_ne!(best_uci(&mut engine, &pos, 4), "d3h7");
+    }
+
+    #[test]
+    fn negative_draw_score_avoids_drawing_capture() {
+        let pos = pos_from_fen("2b1k3/7p/8/8/8/3B4/8/6K1 w - - 0 1");
+
+        let mut engine = Engine::new();
+        engine.set_draw_score(-50);
+        assert_ne!(best_uci(&mut engine, &pos, 4), "d3h7");
+
+        // Overrides contempt instead of stacking on it
+        let mut engine = Engine::new();
+        engine.set_contempt(100);
+        engine.set_draw_score(0);
+        assert_eq!(best_uci(&mut engine, &pos, 4), "d3h7");
+    }
+
+    #[test]
+    fn aspiration_delta_changes_search_tree() {
+        let pos = pos_from_fen("r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N2N2/PP2BPPP/R2QKB1R w KQ - 0 8");
+
+        let mut engine = Engine::new();
+        engine.find_best_move(&pos, &SearchLimits::depth(5));
+        let default_nodes = engine.nodes();
+
+        let mut engine = Engine::new();
+        engine.search_params.aspiration_delta = 5;
+        engine.find_best_move(&pos, &SearchLimits::depth(5));
+        assert_ne!(engine.nodes(), default_nodes);
+    }
+
+    #[test]
+    fn mate_scores_search_the_next_iteration_with_a_full_window() {
+        let engine = Engine::new();
+        assert_eq!(engine.root_window(5, 30000 - 5, Some(120)), (-40000, 40000));
+        assert_eq!(engine.root_window(5, -(30000 - 4), None), (-40000, 40000));
+        let (alpha, beta) = engine.root_window(5, 120, Some(110));
+        assert!(alpha < 120 && beta > 120 && beta - alpha < 1000);
+
+        // Back-rank mate in three, found as a mate score before the final depth
+        let pos = pos_from_fen("6k1/5p1p/6p1/8/8/8/1Q6/1R4K1 w - - 0 1");
+        let mut engine = Engine::new();
+        engine.find_best_move(&pos, &SearchLimits::depth(7));
+        let shortest = engine.root_lines[0].score;
+        assert!(shortest >= 29000, "score {}", shortest);
+        assert_eq!(engine.root_lines[0].pv.len() as i32, 30000 - shortest);
+    }
+
+    #[test]
+    fn a_reduced_quiet_mate_is_not_cut_short_by_an_earlier_longer_one() {
+        // Qg8+ mates in three and is found first. The quiet Kb6 mates in two, but it comes late
+        // in the move list and is searched reduced, so it only shows up from depth 7.
+        let pos = pos_from_fen("1k4r1/6Q1/8/K1p2N1p/4R3/8/8/8 w - - 0 1");
+        let mut engine = Engine::new();
+        assert_eq!(best_uci(&mut engine, &pos, 20), "a5b6");
+        assert_eq!(engine.root_lines[0].score, 30000 - 3);
+        assert!(engine.current_depth < 20);
+    }
+
+    #[test]
+    fn move_assessment_reports_exchange_and_eval_for_white() {
+        let engine = Engine::new();
+        let params = EvalParams::default();
+        let play = |fen: &str, uci: &str| {
+            let pos = pos_from_fen(fen);
+            let m = uci.parse::<shakmaty::uci::UciMove>().unwrap().to_move(&pos).unwrap();
+            (engine.evaluate_move(&pos, &m), pos)
+        };
+
+        // A hanging knight: the exchange wins it and the eval goes up
+        let (free, pos) = play("4k3/8/8/3n4/8/8/8/3QK3 w - - 0 1", "d1d5");
+        assert_eq!(free.see, params.material_value(Role::Knight));
+        assert!(free.eval_after > evaluate(&pos, &params) + 200, "{:?}", free);
+
+        // Defended by a pawn, the queen is lost for it
+        let (defended, _) = play("4k3/8/4p3/3n4/8/8/8/3QK3 w - - 0 1", "d1d5");
+        assert_eq!(defended.see, params.material_value(Role::Knight) - params.material_value(Role::Queen));
+
+        // Black winning material is bad news for White
+        let (black, _) = play("3qk3/8/8/3N4/8/8/8/4K3 b - - 0 1", "d8d5");
+        assert_eq!(black.see, -params.material_value(Role::Knight));
+        assert!(black.eval_after < 0);
+
+        // The rook behind the first one recaptures through it
+        let (xray, _) = play("3rk3/3r4/8/3p4/8/8/3R4/3RK3 w - - 0 1", "d2d5");
+        assert_eq!(xray.see, params.material_value(Role::Pawn) - params.material_value(Role::Rook));
+    }
+
+    #[test]
+    fn shallow_qsearch_skips_small_captures_but_not_hanging_pieces() {
+        let pos = pos_from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
+        let mut full = Engine::new();
+        full.quiescence(&pos, -40000, 40000);
+        let mut shallow = Engine::new();
+        shallow.set_qsearch_mode(QSearchMode::Shallow);
+        shallow.quiescence(&pos, -40000, 40000);
+        assert!(shallow.nodes() < full.nodes(), "{} vs {}", shallow.nodes(), full.nodes());
+
+        let hanging = pos_from_fen("4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1");
+        let score = shallow.quiescence(&hanging, -40000, 40000);
+        assert!(score > 300, "{}", score);
+    }
+
+    #[test]
+    fn game_result_covers_every_way_a_game_ends() {
+        let white_wins = Some(Outcome::Decisive { winner: Color::White });
+        let mated = pos_from_fen("R5k1/5ppp/8/8/8/8/8/6K1 b - - 1 1");
+        assert_eq!(game_result(&mated, &[]), white_wins);
+        let stalemate = pos_from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1");
+        assert_eq!(game_result(&stalemate, &[]), Some(Outcome::Draw));
+        assert!(!is_draw_by_rules(&stalemate, &[]));
+
+        let bare_kings = pos_from_fen("8/8/3k4/8/8/3K4/8/8 w - - 0 1");
+        assert!(is_draw_by_rules(&bare_kings, &[]));
+        assert_eq!(game_result(&bare_kings, &[]), Some(Outcome::Draw));
+
+        let fifty = pos_from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 100 80");
+        assert_eq!(game_result(&fifty, &[]), Some(Outcome::Draw));
+        assert_eq!(game_result(&pos_from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 99 80"), &[]), None);
+        // Mate delivered on the hundredth half-move stands
+        assert_eq!(game_result(&pos_from_fen("R5k1/5ppp/8/8/8/8/8/6K1 b - - 100 80"), &[]), white_wins);
+
+        // Knights out and back twice: the start position comes round a third time
+        let mut pos = Chess::default();
+        let mut history = Vec::new();
+        for (i, uci) in ["g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1", "f6g8"].iter().enumerate() {
+            assert_eq!(game_result(&pos, &history), None, "before move {}", i + 1);
+            let m = uci.parse::<shakmaty::uci::UciMove>().unwrap().to_move(&pos).unwrap();
+            history.push(full_hash(&pos));
+            pos.play_unchecked(&m);
+        }
+        assert!(is_draw_by_rules(&pos, &history));
+        assert_eq!(game_result(&pos, &history), Some(Outcome::Draw));
+    }
+
+    #[test]
+    fn stalemating_capture_is_avoided() {
+        // Qxg4 wins the rook but leaves Black without a move; Qd8+ Rg8 Qxg8# mates instead
+        let pos = pos_from_fen("7k/7p/7P/8/6r1/8/8/K2Q4 w - - 0 1");
+        let mut engine = Engine::new();
+        let m = best_uci(&mut engine, &pos, 4);
+        assert_ne!(m, "d1g4");
+        assert!(engine.root_lines[0].score > 500, "{} {}", m, engine.root_lines[0].score);
+    }
+
+    #[test]
+    fn shorter_mate_is_preferred_at_the_root() {
+        // Mate in 2 for the winner beats mate in 4; the loser prefers being mated in 4
+        assert!(keeps_faster_mate(30000 - 3, 30000 - 7));
+        assert!(!keeps_faster_mate(30000 - 7, 30000 - 3));
+        assert!(!keeps_faster_mate(-(30000 - 4), -(30000 - 8)));
+        assert!(!keeps_faster_mate(120, 30000 - 7));
+
+        // Rb7 or Ra7 mates in 2; plenty of other rook moves mate in 4
+        let pos = pos_from_fen("7k/8/8/8/8/8/R7/1R4K1 w - - 0 1");
+        let mut engine = Engine::new();
+        let m = best_uci(&mut engine, &pos, 6);
+        assert!(m == "b1b7" || m == "a2a7", "{}", m);
+        assert_eq!(engine.root_lines[0].score, 30000 - 3);
+    }
+
+    #[test]
+    fn a_kept_mate_is_reported_with_its_own_line() {
+        let pos = pos_from_fen("7k/8/8/8/8/8/R7/1R4K1 w - - 0 1");
+        let line = |score: i32, ucis: &[&str]| {
+            let mut p = pos.clone();
+            let pv = ucis.iter().map(|uci| {
+                let m = uci.parse::<shakmaty::uci::UciMove>().unwrap().to_move(&p).unwrap();
+                p.play_unchecked(&m);
+                m
+            }).collect();
+            RootLine { score, pv }
+        };
+        let mate_in_2 = line(30000 - 3, &["b1b7", "h8g8", "a2a8"]);
+        let mate_in_3 = || vec![line(30000 - 5, &["a2a6", "h8g7", "b1b7", "g7g8", "a6a8"])];
+
+        // The earlier mate in 2 is played, so its line is the one reported and pondered on
+        let mut engine = Engine::new();
+        engine.set_ponder(true);
+        engine.root_lines = mate_in_3();
+        engine.restore_faster_mate(mate_in_2.clone(), 1);
+        assert_eq!(engine.root_lines.len(), 1);
+        assert_eq!(engine.root_lines[0].score, 30000 - 3);
+        let best = mate_in_2.pv[0].clone();
+        assert_eq!(engine.ponder_move(&best), Some(mate_in_2.pv[1].clone()));
+        assert!(engine.search_summary().contains("score mate 2"), "{}", engine.search_summary());
+
+        // With PreferShortMates off the latest iteration has the last word
+        engine.set_prefer_short_mates(false);
+        engine.root_lines = mate_in_3();
+        engine.restore_faster_mate(mate_in_2, 1);
+        assert_eq!(engine.root_lines[0].score, 30000 - 5);
+    }
+
+    #[test]
+    fn pv_converts_to_san() {
+        let pos = Chess::default();
+        let pv: Vec<Move> = ["g1f3", "b8c6", "e2e4", "e7e5", "f1b5"].iter()
+            .scan(pos.clone(), |p, uci| {
+                let m = uci.parse::<shakmaty::uci::UciMove>().unwrap().to_move(p).unwrap();
+                p.play_unchecked(&m);
+                Some(m)
+            })
+            .collect();
+        assert_eq!(format_pv(&pos, &pv, PvNotation::San), "Nf3 Nc6 e4 e5 Bb5");
+        assert_eq!(format_pv(&pos, &pv, PvNotation::Uci), "g1f3 b8c6 e2e4 e7e5 f1b5");
+    }
+
+    #[test]
+    fn multi_pv_is_limited_to_searchmoves() {
+        let pos = pos_from_fen("r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N2N2/PP2BPPP/R2QKB1R w KQ - 0 8");
+        let search_moves: Vec<Move> = ["a2a3", "h2h3"].iter()
+            .map(|uci| uci.parse::<shakmaty::uci::UciMove>().unwrap().to_move(&pos).unwrap())
+            .collect();
+
+        let mut engine = Engine::new();
+        engine.set_multi_pv(3);
+        engine.find_best_move(&pos, &SearchLimits { depth: 3, search_moves: search_moves.clone(), ..SearchLimits::default() });
+
+        assert_eq!(engine.root_lines.len(), 2);
+        assert!(engine.root_lines.iter().all(|line| search_moves.contains(&line.pv[0])));
+        assert!(engine.root_lines[0].score >= engine.root_lines[1].score);
+        assert_ne!(engine.root_lines[0].pv[0], engine.root_lines[1].pv[0]);
+    }
 
-            let hash = pos.zobrist_hash::<Zobrist64>(shakmaty::EnPassantMode::Always).0;
-            if let Some(entry) = self.tt.get(&hash) {
-                if let Some(ref m) = entry.best_move {
-                    overall_best_move = Some(m.clone());
+    #[test]
+    fn quiescence_sees_quiet_promotion() {
+        // Qxh8 wins a rook but lets b1=Q through; only stopping the pawn holds the extra queen
+        let pos = pos_from_fen("7r/8/k7/8/3Q4/8/1p6/6K1 w - - 0 1");
+        let mut engine = Engine::new();
+        assert_eq!(best_uci(&mut engine, &pos, 1), "d4b2");
+    }
+
+    #[test]
+    fn max_depth_caps_iterative_deepening() {
+        let pos = pos_from_fen("8/8/3k4/8/2PK4/8/8/8 w - - 0 1");
+        let mut engine = Engine::new();
+        engine.set_max_depth(5);
+        assert!(engine.find_best_move(&pos, &SearchLimits::depth(MAX_DEPTH)).is_some());
+        assert_eq!(engine.current_depth, 5);
+    }
+
+    #[test]
+    fn null_move_only_in_quiet_positions_with_pieces() {
+        let quiet = pos_from_fen("r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N2N2/PP2BPPP/R2QKB1R w KQ - 0 8");
+        let null = null_move_position(&quiet).expect("null move allowed");
+        assert_eq!(null.turn(), Color::Black);
+        assert_eq!(null.board(), quiet.board());
+
+        // Side to move in check: passing would leave its king capturable
+        let in_check = pos_from_fen("rnbqkbnr/ppppp1pp/8/5p1Q/4P3/8/PPPP1PPP/RNB1KBNR b KQkq - 1 2");
+        assert!(in_check.is_check());
+        assert!(null_move_position(&in_check).is_none());
+
+        let pawn_ending = pos_from_fen("8/5k2/5p2/8/4P3/5K2/8/8 w - - 0 1");
+        assert!(null_move_position(&pawn_ending).is_none());
+    }
+
+    #[test]
+    fn tactical_moves_match_filtered_legal_moves() {
+        let sorted = |moves: MoveList| {
+            let mut ucis: Vec<String> = moves.iter().map(|m| m.to_uci(CastlingMode::Standard).to_string()).collect();
+            ucis.sort();
+            ucis
+        };
+        // Every node two plies deep from the bench positions, plus promotions, en passant, pins and checks
+        let mut fens: Vec<&str> = crate::bench::BENCH_POSITIONS.to_vec();
+        fens.extend(["1n2k3/P1P5/8/3pP3/8/8/8/4K3 w - d6 0 2", "4k3/8/8/8/1b6/2p1p3/3B4/4K3 w - - 0 1", "4k3/8/8/8/1b6/8/3PP3/4K3 w - - 0 1", "8/8/8/KPp4r/8/8/8/7k w - c6 0 2"]);
+        let mut checked = 0;
+        for fen in fens {
+            let root = pos_from_fen(fen);
+            let after = |pos: &Chess, m: &Move| { let mut next = pos.clone(); next.play_unchecked(m); next };
+            for child in root.legal_moves().iter().map(|m| after(&root, m)) {
+                let grandchildren = child.legal_moves().iter().map(|m| after(&child, m)).collect::<Vec<_>>();
+                for pos in std::iter::once(child).chain(grandchildren) {
+                    let mut expected = pos.legal_moves();
+                    expected.retain(|m| m.is_capture() || matches!(m.promotion(), Some(Role::Queen | Role::Knight)));
+                    assert_eq!(sorted(tactical_moves(&pos)), sorted(expected), "{}", Fen::from_position(pos.clone(), shakmaty::EnPassantMode::Legal));
+                    checked += 1;
                 }
             }
         }
-        overall_best_move
+        assert!(checked > 5_000, "{}", checked);
+    }
+
+    #[test]
+    fn poisoned_tt_move_is_ignored() {
+        let pos = pos_from_fen("r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N2N2/PP2BPPP/R2QKB1R w KQ - 0 8");
+        let hash = full_hash(&pos);
+        // A black move: can never be legal with White to move
+        let foreign = Move::Normal { role: Role::Pawn, from: Square::E7, capture: None, to: Square::E5, promotion: None };
+        assert!(!pos.is_legal(&foreign));
+
+        let mut engine = Engine::new();
+        engine.tt.store(hash, &TTEntry { depth: 0, score: 0, node_type: NodeType::Exact, best_move: Some(foreign), path_dependent: false });
+        let score = engine.alpha_beta(&pos, hash, -40000, 40000, 4, 0, true);
+        assert!(score.abs() < 200);
+
+        let stored = engine.tt.probe(hash).and_then(|e| e.best_move).unwrap();
+        assert!(pos.is_legal(&stored));
+    }
+
+    #[test]
+    fn pv_nodes_store_exact_scores_and_zero_windows_store_bounds() {
+        let pos = pos_from_fen("r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N2N2/PP2BPPP/R2QKB1R w KQ - 0 8");
+        let hash = full_hash(&pos);
+
+        let mut engine = Engine::new();
+        let score = engine.alpha_beta(&pos, hash, -40000, 40000, 4, 0, true);
+        let entry = engine.tt.probe(hash).unwrap();
+        assert!(entry.node_type == NodeType::Exact);
+        assert_eq!(entry.score, score);
+
+        // A zero window has no room for an exact score: it can only fail high or low
+        for (alpha, node_type) in [(score - 50, NodeType::LowerBound), (score + 50, NodeType::UpperBound)] {
+            let mut engine = Engine::new();
+            engine.alpha_beta(&pos, hash, alpha, alpha + 1, 4, 0, false);
+            assert!(engine.tt.probe(hash).unwrap().node_type == node_type);
+        }
+    }
+
+    #[test]
+    fn same_seed_plays_same_game() {
+        let play = |seed: u64| {
+            let mut engine = Engine::new();
+            engine.set_seed(seed);
+            let mut pos = Chess::default();
+            let mut moves = Vec::new();
+            for _ in 0..5 {
+                let m = engine.find_best_move(&pos, &SearchLimits::depth(3)).unwrap();
+                moves.push(m.to_uci(CastlingMode::Standard).to_string());
+                pos.play_unchecked(&m);
+            }
+            moves
+        };
+        assert_eq!(play(42), play(42));
+    }
+
+    #[test]
+    fn node_limit_stops_mid_iteration() {
+        let pos = pos_from_fen("r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N2N2/PP2BPPP/R2QKB1R w KQ - 0 8");
+        let mut engine = Engine::new();
+        let limits = SearchLimits { depth: MAX_DEPTH, nodes: Some(10000), ..SearchLimits::default() };
+        let m = engine.find_best_move(&pos, &limits).unwrap();
+        assert!(pos.is_legal(&m));
+        assert!((10000..10000 + 1024).contains(&engine.nodes()), "searched {} nodes", engine.nodes());
+    }
+
+    #[test]
+    fn movetime_cuts_a_deep_search_short() {
+        let pos = pos_from_fen("r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N2N2/PP2BPPP/R2QKB1R w KQ - 0 8");
+        let mut engine = Engine::new();
+        let limits = SearchLimits { depth: 30, movetime: Some(100), ..SearchLimits::default() };
+        let start = Instant::now();
+        let m = engine.find_best_move(&pos, &limits).unwrap();
+        assert!(pos.is_legal(&m));
+        assert!(start.elapsed() < Duration::from_millis(1_000), "took {:?}", start.elapsed());
+        assert!(engine.current_depth < 30);
+    }
+
+    #[test]
+    fn tiny_budgets_still_finish_depth_one() {
+        let pos = pos_from_fen("r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N2N2/PP2BPPP/R2QKB1R w KQ - 0 8");
+        for limits in [
+            SearchLimits { depth: 30, movetime: Some(0), ..SearchLimits::default() },
+            SearchLimits { depth: 30, movetime: Some(1), ..SearchLimits::default() },
+            SearchLimits { depth: 30, wtime: Some(1), btime: Some(1), ..SearchLimits::default() },
+            SearchLimits { depth: 30, nodes: Some(1), ..SearchLimits::default() },
+        ] {
+            let mut engine = Engine::new();
+            let m = engine.find_best_move(&pos, &limits).unwrap();
+            assert!(pos.is_legal(&m));
+            // Depth 2 was started, so depth 1 ran to the end and the move is a searched one
+            assert!(engine.current_depth >= 2, "{:?}", limits);
+        }
+
+        // Depth 1 rarely reaches the first poll at 1024 nodes; when it does, only stop ends it
+        let mut engine = Engine::new();
+        engine.deadline = Some(Instant::now());
+        engine.node_limit = 1;
+        engine.nodes = 1024;
+        engine.current_depth = 1;
+        assert!(!engine.poll_stop());
+        engine.current_depth = 2;
+        assert!(engine.poll_stop());
+
+        let mut engine = Engine::new();
+        engine.nodes = 1024;
+        engine.current_depth = 1;
+        engine.stop.store(true, Ordering::Relaxed);
+        assert!(engine.poll_stop());
+    }
+
+    #[test]
+    fn eval_kind_defaults_to_hand_crafted_and_survives_an_eval_file() {
+        let pos = pos_from_fen("r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N2N2/PP2BPPP/R2QKB1R w KQ - 0 8");
+        let mut engine = Engine::new();
+        assert_eq!(engine.eval_params.kind, EvalKind::HandCrafted);
+        let hand_crafted = evaluate(&pos, &engine.eval_params);
+
+        engine.set_eval_kind(EvalKind::Nnue);
+        engine.set_eval_params(EvalParams::default());
+        assert_eq!(engine.eval_params.kind, EvalKind::Nnue);
+        // No network yet: the stub answers with the hand-crafted score
+        assert_eq!(evaluate(&pos, &engine.eval_params), hand_crafted);
+    }
+
+    #[test]
+    fn tt_stats_count_the_last_search() {
+        let pos = pos_from_fen("r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N2N2/PP2BPPP/R2QKB1R w KQ - 0 8");
+        let mut engine = Engine::new();
+        // 1 MB holds 65536 entries: a depth 7 search has to evict some
+        engine.set_hash_size(1);
+        engine.find_best_move(&pos, &SearchLimits::depth(7));
+        let stats = engine.tt_stats();
+        assert!(stats.hits > 0 && stats.hits <= stats.probes, "{:?}", stats);
+        assert!(stats.collisions > 0 && stats.collisions <= stats.stores, "{:?}", stats);
+        assert!(stats.occupancy > 0);
+
+        // Counted per search
+        engine.find_best_move(&pos, &SearchLimits::depth(1));
+        assert!(engine.tt_stats().stores < stats.stores);
+    }
+
+    #[test]
+    fn reset_keeps_options_but_clears_search_state() {
+        let pos = pos_from_fen("r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N2N2/PP2BPPP/R2QKB1R w KQ - 0 8");
+        let mut engine = Engine::new();
+        engine.set_hash_size(16);
+        engine.set_contempt(30);
+        engine.find_best_move(&pos, &SearchLimits::depth(4));
+        assert!(engine.tt.probe(full_hash(&pos)).is_some());
+
+        engine.reset();
+        assert!(engine.tt.probe(full_hash(&pos)).is_none());
+        assert_eq!(engine.tt.hashfull(), 0);
+        assert!(engine.history.iter().flatten().flatten().all(|&h| h == 0));
+        assert_eq!(engine.hash_mb, 16);
+        assert_eq!(engine.contempt, 30);
+    }
+
+    #[test]
+    fn capture_ordering_follows_the_selected_scheme() {
+        // Nxb5 takes a loose queen, exf5 a knight for nothing much, Qxd7+ a rook the king takes back
+        let pos = pos_from_fen("4k3/3r4/6p1/1q3n2/4P3/2N5/8/3Q2K1 w - - 0 1");
+        let captures_in_order = |ordering| {
+            let mut engine = Engine::new();
+            engine.set_capture_ordering(ordering);
+            let mut moves = pos.legal_moves();
+            engine.order_moves(&pos, &mut moves, None, 0);
+            moves.iter().filter(|m| m.is_capture()).map(|m| m.to_uci(CastlingMode::Standard).to_string()).collect::<Vec<_>>()
+        };
+        assert_eq!(captures_in_order(CaptureOrdering::See), ["c3b5", "e4f5", "d1d7"]);
+        assert_eq!(captures_in_order(CaptureOrdering::MvvLva), ["c3b5", "d1d7", "e4f5"]);
+        // Captures come ahead of every quiet move either way
+        let mut moves = pos.legal_moves();
+        Engine::new().order_moves(&pos, &mut moves, None, 0);
+        assert!(moves.iter().take(3).all(|m| m.is_capture()));
+    }
+
+    #[test]
+    fn history_adjusts_late_move_reductions() {
+        let params = SearchParams::default();
+        assert_eq!(params.lmr_reduction(8, 9, 500), 3);
+        assert_eq!(params.lmr_reduction(8, 9, params.lmr_history_high), 2);
+        assert_eq!(params.lmr_reduction(8, 9, 0), 4);
+        assert_eq!(params.lmr_reduction(4, 3, params.lmr_history_high), 1);
+
+        // The search reads the mover's own history: over the same late quiets, a proven move is
+        // reduced less and one that never caused a cutoff more
+        let pos = pos_from_fen("r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N2N2/PP2BPPP/R2QKB1R w KQ - 0 8");
+        let quiets: Vec<Move> = pos.legal_moves().into_iter().filter(|m| !m.is_capture()).collect();
+        let reductions = |engine: &Engine| -> Vec<u32> {
+            quiets.iter().enumerate().map(|(i, m)| engine.late_move_reduction(Color::White, m, i + params.lmr_min_move, 9)).collect()
+        };
+        let mut engine = Engine::new();
+        engine.history[0] = [[500; 64]; 64];
+        let average = reductions(&engine);
+        engine.history[0] = [[params.lmr_history_high; 64]; 64];
+        let proven = reductions(&engine);
+        engine.history[0] = [[0; 64]; 64];
+        let failed = reductions(&engine);
+        for i in 0..quiets.len() {
+            assert_eq!(proven[i], (average[i] - 1).max(1));
+            assert_eq!(failed[i], average[i] + 1);
+        }
+        // Black's table is not consulted for White's moves
+        engine.history[1] = [[params.lmr_history_high; 64]; 64];
+        assert_eq!(reductions(&engine), failed);
+    }
+
+    #[test]
+    fn quiets_before_a_cutoff_lose_history() {
+        let pos = Chess::default();
+        let uci = |s: &str| s.parse::<shakmaty::uci::UciMove>().unwrap().to_move(&pos).unwrap();
+        let idx = |m: &Move| (m.from().unwrap() as usize, m.to() as usize);
+        let (a3, h3, e4) = (uci("a2a3"), uci("h2h3"), uci("e2e4"));
+
+        let mut engine = Engine::new();
+        engine.update_history(Color::White, &e4, &[a3.clone(), h3.clone()], 4);
+        let history = &engine.history[0];
+        assert_eq!(history[idx(&e4).0][idx(&e4).1], 16);
+        assert_eq!(history[idx(&a3).0][idx(&a3).1], -16);
+        assert_eq!(history[idx(&h3).0][idx(&h3).1], -16);
+        assert!(engine.history[1].iter().flatten().all(|&h| h == 0));
+
+        // Repeated deep cutoffs approach the bound without passing it
+        for _ in 0..1000 {
+            engine.update_history(Color::White, &e4, std::slice::from_ref(&a3), 60);
+        }
+        let e4_score = engine.history[0][idx(&e4).0][idx(&e4).1];
+        assert!(e4_score > HISTORY_MAX / 2 && e4_score <= HISTORY_MAX, "{}", e4_score);
+        assert!(engine.history[0][idx(&a3).0][idx(&a3).1] >= -HISTORY_MAX);
+    }
+
+    #[test]
+    fn eval_noise_varies_root_choice_by_seed() {
+        let pos = pos_from_fen("r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N2N2/PP2BPPP/R2QKB1R w KQ - 0 8");
+        let pick = |seed: u64, noise: i32| {
+            let mut engine = Engine::new();
+            engine.set_seed(seed);
+            engine.set_eval_noise(noise);
+            best_uci(&mut engine, &pos, 3)
+        };
+
+        let quiet: Vec<String> = (0..8).map(|seed| pick(seed, 0)).collect();
+        assert!(quiet.iter().all(|m| *m == quiet[0]));
+
+        let noisy: std::collections::HashSet<String> = (0..8).map(|seed| pick(seed, 50)).collect();
+        assert!(noisy.len() > 1);
+        assert_eq!(pick(3, 50), pick(3, 50));
+    }
+
+    #[test]
+    fn eval_noise_leaves_mate_scores_and_stored_scores_alone() {
+        // Rb7 or Ra7 mates in 2; noise must not let a mate in 4 overtake them
+        let pos = pos_from_fen("7k/8/8/8/8/8/R7/1R4K1 w - - 0 1");
+        for seed in 0..8 {
+            let mut engine = Engine::new();
+            engine.set_seed(seed);
+            engine.set_eval_noise(100);
+            let m = best_uci(&mut engine, &pos, 6);
+            assert!(m == "b1b7" || m == "a2a7", "seed {}: {}", seed, m);
+            assert_eq!(engine.root_lines[0].score, 30000 - 3);
+        }
+
+        // Away from mates the root line and its table entry carry the searched score
+        let pos = pos_from_fen("r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N2N2/PP2BPPP/R2QKB1R w KQ - 0 8");
+        let mut quiet = Engine::new();
+        let quiet_move = best_uci(&mut quiet, &pos, 4);
+        for seed in 0..8 {
+            let mut engine = Engine::new();
+            engine.set_seed(seed);
+            engine.set_eval_noise(100);
+            let m = best_uci(&mut engine, &pos, 4);
+            let stored = engine.tt.probe(full_hash(&pos)).unwrap();
+            assert_eq!(stored.score, engine.root_lines[0].score);
+            if m == quiet_move {
+                assert_eq!(engine.root_lines[0].score, quiet.root_lines[0].score);
+            }
+        }
+    }
+
+    #[test]
+    fn root_noise_never_cuts_off_the_root() {
+        // Noise far above the window on every move: a real score inside it must still search
+        // every root move and store an exact score, just as without noise
+        let pos = pos_from_fen("r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N2N2/PP2BPPP/R2QKB1R w KQ - 0 8");
+        let hash = full_hash(&pos);
+        let score = Engine::new().alpha_beta(&pos, hash, -40000, 40000, 3, 0, true);
+
+        let mut quiet = Engine::new();
+        let quiet_score = quiet.alpha_beta(&pos, hash, score - 10, score + 10, 3, 0, true);
+        let mut noisy = Engine::new();
+        noisy.root_noise = pos.legal_moves().into_iter().map(|m| (m, 1000)).collect();
+        let noisy_score = noisy.alpha_beta(&pos, hash, score - 10, score + 10, 3, 0, true);
+
+        assert_eq!(noisy_score, quiet_score);
+        assert_eq!(noisy.nodes, quiet.nodes);
+        let entry = noisy.tt.probe(hash).unwrap();
+        assert!(entry.node_type == NodeType::Exact);
+        assert_eq!(entry.score, score);
+        assert_eq!(entry.best_move, quiet.tt.probe(hash).unwrap().best_move);
+    }
+
+    #[test]
+    fn repetition_scores_do_not_leak_across_paths() {
+        // Kg1 is White's only king move. If the game already saw that position it is a repetition
+        // draw, otherwise White is simply a queen down. b4 is there so the root is actually searched.
+        let pos = pos_from_fen("7k/8/8/8/8/1P6/q7/7K w - - 10 60");
+        let mut after = pos.clone();
+        let kg1 = pos.legal_moves().into_iter().find(|m| m.role() == Role::King).unwrap();
+        after.play_unchecked(&kg1);
+
+        let mut engine = Engine::new();
+        engine.set_game_history(vec![full_hash(&after)]);
+        engine.find_best_move(&pos, &SearchLimits::depth(4));
+        assert_eq!(engine.root_lines[0].score, 0);
+        assert!(engine.tt.probe(full_hash(&pos)).unwrap().path_dependent);
+
+        // Same TT, no history: the cached draw must not be reused
+        engine.set_game_history(Vec::new());
+        engine.find_best_move(&pos, &SearchLimits::depth(4));
+        assert!(engine.root_lines[0].score < -500, "score {}", engine.root_lines[0].score);
+    }
+
+    #[test]
+    fn pushed_history_makes_a_repetition_a_draw() {
+        // A queen down, White shuffles Kg1 Kg8 Kh1 Kh8 twice; Kg1 again repeats for the third time
+        let start = pos_from_fen("7k/8/8/8/8/1P6/q7/7K w - - 10 60");
+        let mut engine = Engine::new();
+        let mut pos = start.clone();
+        for uci in ["h1g1", "h8g8", "g1h1", "g8h8"].repeat(2) {
+            let m = uci.parse::<shakmaty::uci::UciMove>().unwrap().to_move(&pos).unwrap();
+            engine.push_position(full_hash(&pos));
+            pos.play_unchecked(&m);
+        }
+        assert_eq!(pos.board(), start.board());
+        engine.find_best_move(&pos, &SearchLimits::depth(4));
+        assert_eq!(engine.root_lines[0].score, 0);
+
+        // Taking the moves back leaves nothing to repeat
+        while engine.pop_position().is_some() {}
+        engine.find_best_move(&start, &SearchLimits::depth(4));
+        assert!(engine.root_lines[0].score < -500, "score {}", engine.root_lines[0].score);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn threads_option_is_clamped() {
+        let cores = std::thread::available_parallelism().map_or(1, |n| n.get()).min(MAX_THREADS);
+        let mut engine = Engine::new();
+        engine.set_threads(0);
+        assert_eq!(engine.threads, 1);
+        engine.set_threads(100_000);
+        assert_eq!(engine.threads, cores);
+
+        // Threads=1 is the plain single-threaded search
+        let pos = pos_from_fen("r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N2N2/PP2BPPP/R2QKB1R w KQ - 0 8");
+        let mut plain = Engine::new();
+        let plain_move = best_uci(&mut plain, &pos, 4);
+        let mut one = Engine::new();
+        one.set_threads(1);
+        assert_eq!(best_uci(&mut one, &pos, 4), plain_move);
+        assert_eq!(one.nodes(), plain.nodes());
+    }
+
+    #[test]
+    fn single_legal_move_is_played_without_searching() {
+        // Kxb2 is the only way out
+        let pos = pos_from_fen("k7/8/8/8/8/8/1r6/K7 w - - 0 1");
+        let mut engine = Engine::new();
+        assert_eq!(best_uci(&mut engine, &pos, MAX_DEPTH), "a1b2");
+        assert_eq!(engine.nodes(), 0);
+    }
+
+    #[test]
+    fn found_mate_ends_iterative_deepening() {
+        let pos = pos_from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+        let mut engine = Engine::new();
+        assert_eq!(best_uci(&mut engine, &pos, MAX_DEPTH), "a1a8");
+        assert_eq!(engine.current_depth, 1);
+    }
+
+    #[test]
+    fn pv_is_the_searched_mating_line() {
+        // Rb7 (or Ra7) and mate on the back rank next move
+        let pos = pos_from_fen("7k/8/8/8/8/8/R7/1R4K1 w - - 0 1");
+        let mut engine = Engine::new();
+        engine.find_best_move(&pos, &SearchLimits::depth(5));
+        let pv = engine.root_lines[0].pv.clone();
+        assert_eq!(pv.len(), 3, "{}", format_pv(&pos, &pv, PvNotation::Uci));
+
+        let mut line = pos.clone();
+        for m in &pv {
+            assert!(line.is_legal(m));
+            line.play_unchecked(m);
+        }
+        assert!(line.is_checkmate());
+    }
+
+    #[test]
+    fn time_allocation_uses_the_side_to_move_clock() {
+        let clock = SearchLimits { wtime: Some(60_000), btime: Some(3_000), winc: 1_000, ..SearchLimits::default() };
+        assert_eq!(clock.allocate_time(Color::White, DEFAULT_SLOW_MOVER), Some(60_000 / DEFAULT_MOVES_TO_GO + 1_000));
+        assert_eq!(clock.allocate_time(Color::Black, DEFAULT_SLOW_MOVER), Some(3_000 / DEFAULT_MOVES_TO_GO));
+        // Never more than is left on the clock
+        let short = SearchLimits { wtime: Some(200), winc: 5_000, ..SearchLimits::default() };
+        assert_eq!(short.allocate_time(Color::White, DEFAULT_SLOW_MOVER), Some(200 - MOVE_OVERHEAD_MS));
+
+        let movetime = SearchLimits { movetime: Some(1_000), ..clock.clone() };
+        assert_eq!(movetime.allocate_time(Color::Black, DEFAULT_SLOW_MOVER), Some(1_000 - MOVE_OVERHEAD_MS));
+        assert_eq!(SearchLimits { infinite: true, ..clock }.allocate_time(Color::White, DEFAULT_SLOW_MOVER), None);
+        assert_eq!(SearchLimits::default().allocate_time(Color::White, DEFAULT_SLOW_MOVER), None);
+    }
+
+    #[test]
+    fn slow_mover_scales_the_clock_share() {
+        let clock = SearchLimits { wtime: Some(300_000), btime: Some(300_000), ..SearchLimits::default() };
+        let base = clock.allocate_time(Color::White, DEFAULT_SLOW_MOVER).unwrap();
+        assert_eq!(clock.allocate_time(Color::White, 200), Some(2 * base));
+        assert_eq!(clock.allocate_time(Color::White, 50), Some(base / 2));
+        // Never past what is left on the clock, and movetime is taken as given
+        assert_eq!(SearchLimits { wtime: Some(1_000), movestogo: Some(1), ..clock.clone() }.allocate_time(Color::White, 200), Some(1_000 - MOVE_OVERHEAD_MS));
+        assert_eq!(SearchLimits { movetime: Some(1_000), ..clock }.allocate_time(Color::White, 200), Some(1_000 - MOVE_OVERHEAD_MS));
+    }
+
+    #[test]
+    fn movestogo_paces_to_the_time_control() {
+        let clock = |movestogo| SearchLimits { wtime: Some(60_000), btime: Some(60_000), movestogo: Some(movestogo), ..SearchLimits::default() };
+        // Last move before the control: everything but the overhead
+        assert_eq!(clock(1).allocate_time(Color::White, DEFAULT_SLOW_MOVER), Some(60_000 - MOVE_OVERHEAD_MS));
+        assert_eq!(clock(5).allocate_time(Color::White, DEFAULT_SLOW_MOVER), Some(12_000));
+        assert_eq!(clock(40).allocate_time(Color::Black, DEFAULT_SLOW_MOVER), Some(1_500));
+        assert!(clock(40).allocate_time(Color::White, DEFAULT_SLOW_MOVER) < SearchLimits { movestogo: None, ..clock(40) }.allocate_time(Color::White, DEFAULT_SLOW_MOVER));
+        assert_eq!(clock(0).allocate_time(Color::White, DEFAULT_SLOW_MOVER), clock(1).allocate_time(Color::White, DEFAULT_SLOW_MOVER));
+    }
+
+    #[test]
+    fn null_move_pruning_can_be_switched_off() {
+        // Kh6 wins by zugzwang. Null moves let Black pass, which it can't really do, and hide it at depth 7
+        let pos = pos_from_fen("1q1k4/2Rr4/8/2Q3K1/8/8/8/8 w - - 0 1");
+        let mut pruned = Engine::new();
+        pruned.find_best_move(&pos, &SearchLimits::depth(7));
+
+        let mut full = Engine::new();
+        full.set_null_move(false);
+        assert_eq!(best_uci(&mut full, &pos, 7), "g5h6");
+        assert_ne!(full.nodes(), pruned.nodes());
+    }
+
+    #[test]
+    fn analyse_mode_turns_off_null_move_and_futility_pruning() {
+        let pos = pos_from_fen("r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N2N2/PP2BPPP/R2QKB1R w KQ - 0 8");
+        let mut normal = Engine::new();
+        normal.find_best_move(&pos, &SearchLimits::depth(5));
+
+        let mut analysing = Engine::new();
+        analysing.set_analyse_mode(true);
+        analysing.find_best_move(&pos, &SearchLimits::depth(5));
+
+        // The same tree as switching both prunings off by hand
+        let mut unpruned = Engine::new();
+        unpruned.set_null_move(false);
+        unpruned.search_params.futility_margin = i32::MAX / 2;
+        unpruned.find_best_move(&pos, &SearchLimits::depth(5));
+
+        assert_eq!(analysing.nodes(), unpruned.nodes());
+        assert!(analysing.nodes() > normal.nodes());
+    }
+
+    #[test]
+    fn perpetual_check_saves_a_lost_position() {
+        // Black is two rooks up with mate threats on f2 and g2; Qf7+ Kh8 Qf8+ Kh7 repeats
+        let pos = pos_from_fen("8/7k/6pp/3Q4/q7/8/2rr1PPP/6K1 w - - 0 1");
+        assert!(evaluate(&pos, &EvalParams::default()) < -500);
+
+        let mut engine = Engine::new();
+        assert_eq!(best_uci(&mut engine, &pos, 6), "d5f7");
+        assert_eq!(engine.root_lines[0].score, 0);
+
+        // Playing for a win doesn't change that: the draw is still far better than the alternative
+        let mut engine = Engine::new();
+        engine.set_contempt(50);
+        assert_eq!(best_uci(&mut engine, &pos, 6), "d5f7");
+        assert_eq!(engine.root_lines[0].score, -50);
+    }
+}
@@ -1,42 +1,621 @@
-use shakmaty::{Chess, Role, Position, Square, Color, Bitboard, Rank};
+use shakmaty::{attacks, Board, ByColor, CastlingMode, Chess, EnPassantMode, Role, Position, Square, Color, Bitboard, Rank, File};
 use crate::constants::*;
+use crate::params::{EvalKind, EvalMode, EvalParams, PstSet};
 
-fn get_pst_value(role: Role, color: Color, square: Square, phase: i32) -> i32 {
+// Tables are written as seen from White with rank 8 first, so a8 is index 0 and h1 index 63.
+// White reads rank r at row 7 - r; Black reads row r, i.e. the board flipped vertically (files
+// are not mirrored), which gives every role, kings included, the same value on mirrored squares.
+fn get_pst_value(pst: &PstSet, role: Role, color: Color, square: Square, phase: i32) -> i32 {
     let index = if color == Color::White {
         (7 - square.rank() as usize) * 8 + (square.file() as usize)
     } else {
         (square.rank() as usize) * 8 + (square.file() as usize)
     };
 
-    let (mg, eg) = match role {
-        Role::Pawn => (MG_PAWN_PST[index], EG_PAWN_PST[index]),
-        Role::Knight => (MG_KNIGHT_PST[index], MG_KNIGHT_PST[index]),
-        Role::Bishop => (MG_BISHOP_PST[index], MG_BISHOP_PST[index]),
-        Role::Rook => (ROOK_PST[index], ROOK_PST[index]),
-        Role::Queen => (QUEEN_PST[index], QUEEN_PST[index]),
-        Role::King => (MG_KING_PST[index], EG_KING_PST[index]),
-    };
+    let (mg, eg) = pst.get(role, index);
 
     ((mg * (256 - phase)) + (eg * phase)) / 256
 }
 
-pub fn evaluate(pos: &Chess) -> i32 {
-    if pos.is_game_over() {
-        if pos.is_checkmate() { return -30000; }
-        return 0;
+// Central file (c-f) on the 4th rank or beyond, relative to `color`, defended by one of its pawns.
+// Ranks are mirrored for Black, so a black outpost on d4 matches a white one on d5.
+fn is_outpost(square: Square, color: Color, board: &Board) -> bool {
+    let rel_rank = if color == Color::White { square.rank() as i32 } else { 7 - square.rank() as i32 };
+    let is_central_file = square.file() >= File::C && square.file() <= File::F;
+    if !is_central_file || rel_rank < 3 { return false; }
+
+    let defenders = attacks::pawn_attacks(color.other(), square) & board.pawns() & board.by_color(color);
+    defenders.any()
+}
+
+// Ranks strictly in front of `square` from `color`'s point of view
+fn ranks_ahead(square: Square, color: Color) -> Bitboard {
+    let mut ahead = Bitboard(0);
+    if color == Color::White {
+        for r in (square.rank() as u32 + 1)..8 {
+            ahead |= Bitboard::from_rank(Rank::new(r));
+        }
+    } else {
+        for r in 0..(square.rank() as u32) {
+            ahead |= Bitboard::from_rank(Rank::new(r));
+        }
+    }
+    ahead
+}
+
+fn adjacent_files(file: File) -> Bitboard {
+    file.offset(-1).map_or(Bitboard(0), Bitboard::from_file) |
+    file.offset(1).map_or(Bitboard(0), Bitboard::from_file)
+}
+
+fn is_passed(square: Square, color: Color, enemy_pawns: Bitboard) -> bool {
+    let files = Bitboard::from_file(square.file()) | adjacent_files(square.file());
+    (enemy_pawns & files & ranks_ahead(square, color)).is_empty()
+}
+
+// What a passed pawn loses to the enemy piece sitting on its stop square, if any
+fn blockade_penalty(board: &Board, square: Square, color: Color, params: &EvalParams) -> i32 {
+    let Some(stop) = square.offset(if color == Color::White { 8 } else { -8 }) else { return 0; };
+    match board.piece_at(stop) {
+        Some(blocker) if blocker.color != color => -params.blockader_bonus[blocker.role as usize - 1],
+        _ => 0,
+    }
+}
+
+fn is_isolated(square: Square, my_pawns: Bitboard) -> bool {
+    (my_pawns & adjacent_files(square.file())).is_empty()
+}
+
+// No friendly pawn beside or behind it to ever support it, and its stop square is covered by an enemy pawn
+fn is_backward(square: Square, color: Color, my_pawns: Bitboard, enemy_pawns: Bitboard) -> bool {
+    if (my_pawns & adjacent_files(square.file()) & !ranks_ahead(square, color)).any() { return false; }
+    let Some(stop) = square.offset(if color == Color::White { 8 } else { -8 }) else { return false; };
+    (attacks::pawn_attacks(color, stop) & enemy_pawns).any()
+}
+
+// Bases of locked pawn chains: pawns that support a friendly pawn diagonally ahead but have no
+// pawn supporting them, where some pawn up the chain is blocked head-on by an enemy pawn. The
+// front can't advance to relieve the base, so it is the chain's lasting target (d4 in the French).
+fn chain_bases(board: &Board, color: Color) -> Bitboard {
+    let ours = board.pawns() & board.by_color(color);
+    let theirs = board.pawns() & board.by_color(color.other());
+    let forward = if color == Color::White { 8 } else { -8 };
+    let mut bases = Bitboard(0);
+    for square in ours {
+        let supported = (attacks::pawn_attacks(color.other(), square) & ours).any();
+        if supported || (attacks::pawn_attacks(color, square) & ours).is_empty() { continue; }
+
+        let mut chain = Bitboard::from(square);
+        let mut front = chain;
+        while front.any() {
+            let next = front.into_iter().fold(Bitboard(0), |acc, sq| acc | attacks::pawn_attacks(color, sq)) & ours & !chain;
+            chain |= next;
+            front = next;
+        }
+        if chain.into_iter().any(|sq| sq.offset(forward).is_some_and(|stop| theirs.contains(stop))) {
+            bases |= Bitboard::from(square);
+        }
+    }
+    bases
+}
+
+// How far the pawns have come, summed: the room behind them to manoeuvre in
+fn pawn_space(board: &Board, color: Color) -> i32 {
+    (board.pawns() & board.by_color(color)).into_iter()
+        .map(|sq| if color == Color::White { sq.rank() as i32 - 1 } else { 6 - sq.rank() as i32 })
+        .sum()
+}
+
+// Locked chain bases, and unresolved tension (pawns that can take each other) credited to the
+// side with more space behind it, who can better afford to keep it and choose when to resolve
+fn pawn_chain_score(board: &Board, color: Color, params: &EvalParams) -> i32 {
+    let mut score = chain_bases(board, color).count() as i32 * params.chain_base_penalty;
+    if pawn_space(board, color) > pawn_space(board, color.other()) {
+        let ours = board.pawns() & board.by_color(color);
+        let theirs = board.pawns() & board.by_color(color.other());
+        let tension: u32 = ours.into_iter().map(|sq| (attacks::pawn_attacks(color, sq) & theirs).count() as u32).sum();
+        score += tension as i32 * params.pawn_tension_bonus;
+    }
+    score
+}
+
+// For one pawn of a doubled pair. The capture that doubled it usually opened a neighbouring
+// file, which is some compensation once one of our rooks is using it.
+fn doubled_pawn_score(board: &Board, square: Square, color: Color, params: &EvalParams) -> i32 {
+    let my_pawns = board.pawns() & board.by_color(color);
+    let my_rooks = board.rooks() & board.by_color(color);
+    let mut score = params.doubled_pawn_penalty[square.file() as usize];
+    if is_isolated(square, my_pawns) {
+        score += params.isolated_doubled_penalty;
+    } else if adjacent_files(square.file()).into_iter().any(|sq| my_rooks.contains(sq) && (my_pawns & Bitboard::from_file(sq.file())).is_empty()) {
+        score += params.doubled_pawn_rook_compensation;
+    }
+    score
+}
+
+// Attacks of every piece on the board, generated once per evaluate call for the terms that need them
+struct AttackInfo {
+    from: [Bitboard; 64],
+    pawns: ByColor<Bitboard>,
+    by_color: ByColor<Bitboard>,
+}
+
+impl AttackInfo {
+    // Pawns are done a whole side at a time with shifts; no term needs a single pawn's attacks
+    fn new(board: &Board) -> Self {
+        let white_pawns = board.pawns() & board.white();
+        let black_pawns = board.pawns() & board.black();
+        let pawns = ByColor {
+            white: (white_pawns & !Bitboard::from_file(File::H)).shift(9) | (white_pawns & !Bitboard::from_file(File::A)).shift(7),
+            black: (black_pawns & !Bitboard::from_file(File::A)).shift(-9) | (black_pawns & !Bitboard::from_file(File::H)).shift(-7),
+        };
+        let mut info = Self { from: [Bitboard(0); 64], pawns, by_color: pawns };
+        for color in Color::ALL {
+            for square in board.by_color(color) & !board.pawns() {
+                let attacks = board.attacks_from(square);
+                info.from[square as usize] = attacks;
+                *info.by_color.get_mut(color) |= attacks;
+            }
+        }
+        info
+    }
+
+    fn from(&self, square: Square) -> Bitboard {
+        self.from[square as usize]
+    }
+}
+
+// Rook file terms: open or semi-open file (halved when an enemy rook contests it), plus
+// pressure on an isolated or backward enemy pawn the rook sees down the file.
+fn rook_file_score(board: &Board, attacks: &AttackInfo, square: Square, color: Color, params: &EvalParams) -> i32 {
+    let file_bb = Bitboard::from_file(square.file());
+    let my_pawns = board.pawns() & board.by_color(color);
+    let enemy_pawns = board.pawns() & board.by_color(color.other());
+
+    let mut score = if (board.pawns() & file_bb).is_empty() {
+        params.rook_on_open_file
+    } else if (my_pawns & file_bb).is_empty() {
+        params.rook_on_semi_open_file
+    } else {
+        0
+    };
+    if (board.rooks() & board.by_color(color.other()) & file_bb).any() {
+        score /= 2;
+    }
+
+    let targets = attacks.from(square) & file_bb & enemy_pawns;
+    if targets.into_iter().any(|sq| is_isolated(sq, enemy_pawns) || is_backward(sq, color.other(), enemy_pawns, my_pawns)) {
+        score += params.rook_attacks_weak_pawn;
+    }
+    score
+}
+
+// Rook on the same file as a passed pawn and behind it (relative to the pawn's direction):
+// it pushes a friendly passer forward and keeps an enemy one in check from the rear.
+fn rook_behind_passer(board: &Board, square: Square) -> bool {
+    let file_pawns = board.pawns() & Bitboard::from_file(square.file());
+    file_pawns.into_iter().any(|pawn_sq| {
+        let Some(color) = board.color_at(pawn_sq) else { return false; };
+        is_passed(pawn_sq, color, board.pawns() & board.by_color(color.other()))
+            && ranks_ahead(square, color).contains(pawn_sq)
+    })
+}
+
+// Rooks and queens on the enemy's back two ranks, together: each one there makes the others
+// worth more (quadratic in their number), and more again with the king still stuck behind them
+fn heavy_infiltration(board: &Board, color: Color, params: &EvalParams) -> i32 {
+    let back_two = if color == Color::White {
+        Bitboard::from_rank(Rank::Seventh) | Bitboard::from_rank(Rank::Eighth)
+    } else {
+        Bitboard::from_rank(Rank::Second) | Bitboard::from_rank(Rank::First)
+    };
+    let count = (board.rooks_and_queens() & board.by_color(color) & back_two).count() as i32;
+    let score = count * count * params.heavy_infiltration_bonus;
+    if board.king_of(color.other()).is_some_and(|king| back_two.contains(king)) {
+        score * params.confined_king_factor
+    } else {
+        score
     }
+}
 
+// -1 = queenside (a-c), 1 = kingside (f-h), 0 = centre
+fn wing(file: File) -> i32 {
+    if file <= File::C { -1 } else if file >= File::F { 1 } else { 0 }
+}
+
+// Only relevant with kings on opposite wings: `attacker`'s pawns advancing on the enemy
+// king's files, plus the holes in the defender's shield (charged to the defender).
+fn pawn_storm(board: &Board, attacker: Color, params: &EvalParams) -> i32 {
+    let (Some(my_king), Some(their_king)) = (board.king_of(attacker), board.king_of(attacker.other())) else { return 0; };
+    let (my_wing, their_wing) = (wing(my_king.file()), wing(their_king.file()));
+    if my_wing == 0 || their_wing == 0 || my_wing == their_wing { return 0; }
+
+    let mut bonus = 0;
+    let their_pawns = board.pawns() & board.by_color(attacker.other());
+    for delta in -1..=1 {
+        if let Some(file) = their_king.file().offset(delta) {
+            let file_bb = Bitboard::from_file(file);
+            for sq in board.pawns() & board.by_color(attacker) & file_bb {
+                let rel_rank = if attacker == Color::White { sq.rank() as usize } else { 7 - sq.rank() as usize };
+                bonus += params.pawn_storm_bonus[rel_rank];
+            }
+            if (their_pawns & file_bb).is_empty() {
+                bonus -= params.shield_hole_penalty;
+            }
+        }
+    }
+    bonus
+}
+
+// The shield only counts pawns on their home rank. A king-side (or queen-side) g3/b3 pawn is
+// mostly compensated by a bishop on g2/b2 behind it; with that bishop gone it leaves a hole.
+fn fianchetto_score(board: &Board, color: Color, king: Square, params: &EvalParams) -> i32 {
+    let file = match wing(king.file()) {
+        1 => File::G,
+        -1 => File::B,
+        _ => return 0,
+    };
+    let (home, advanced) = if color == Color::White { (Rank::Second, Rank::Third) } else { (Rank::Seventh, Rank::Sixth) };
+    let mine = board.by_color(color);
+    if !(board.pawns() & mine).contains(Square::from_coords(file, advanced)) { return 0; }
+
+    if (board.bishops() & mine).contains(Square::from_coords(file, home)) {
+        params.fianchetto_bonus
+    } else {
+        params.fianchetto_hole_penalty
+    }
+}
+
+// Open and semi-open files on and next to `color`'s king, worse when an enemy rook or queen is on them.
+fn king_file_exposure(board: &Board, color: Color, params: &EvalParams) -> i32 {
+    let Some(king) = board.king_of(color) else { return 0; };
+    let heavy = (board.rooks() | board.queens()) & board.by_color(color.other());
+
+    let mut penalty = 0;
+    for delta in -1..=1 {
+        if let Some(file) = king.file().offset(delta) {
+            let file_bb = Bitboard::from_file(file);
+            if (board.pawns() & board.by_color(color) & file_bb).any() { continue; }
+            let file_penalty = if (board.pawns() & file_bb).is_empty() {
+                params.king_open_file_penalty
+            } else {
+                params.king_semi_open_file_penalty
+            };
+            penalty += if (heavy & file_bb).any() { 2 * file_penalty } else { file_penalty };
+        }
+    }
+    penalty
+}
+
+// A king with few safe squares to step to is one check away from a mating net: charged to the
+// king's side per safe move short of KING_FLIGHT_SQUARES, faded out toward the endgame.
+fn king_flight_score(board: &Board, attacks: &AttackInfo, color: Color, phase: i32, params: &EvalParams) -> i32 {
+    let Some(king) = board.king_of(color) else { return 0; };
+    let flights = attacks::king_attacks(king) & !board.by_color(color) & !*attacks.by_color.get(color.other());
+    let missing = KING_FLIGHT_SQUARES.saturating_sub(flights.count() as u32) as i32;
+    missing * params.king_flight_penalty * (256 - phase) / 256
+}
+
+// Wing pawn structure, a-c and f-h (the centre files are left to the other pawn terms).
+// More pawns than the enemy on distinct files is a healthy majority that can make an outside
+// passer; fewer pawns with one already pushed to the 4th rank or beyond is a minority attack.
+fn pawn_majority(board: &Board, color: Color, params: &EvalParams) -> i32 {
+    let mine = board.pawns() & board.by_color(color);
+    let theirs = board.pawns() & board.by_color(color.other());
+    let fourth_and_beyond = if color == Color::White { Bitboard(0xFFFF_FFFF_FF00_0000) } else { Bitboard(0x0000_00FF_FFFF_FFFF) };
+
+    let mut score = 0;
+    for files in [[File::A, File::B, File::C], [File::F, File::G, File::H]] {
+        let wing = files.iter().fold(Bitboard(0), |bb, &f| bb | Bitboard::from_file(f));
+        let (my_count, their_count) = ((mine & wing).count(), (theirs & wing).count());
+        let my_files = files.iter().filter(|&&f| (mine & Bitboard::from_file(f)).any()).count();
+
+        // Against no pawns at all it's just passers, which the passed pawn term already covers
+        if their_count > 0 && my_count > their_count && my_files > their_count {
+            score += params.pawn_majority_bonus;
+        } else if my_count > 0 && my_count < their_count && (mine & wing & fourth_and_beyond).any() {
+            score += params.minority_attack_bonus;
+        }
+    }
+    score
+}
+
+// Mop-up against a lone king when we have at least a rook: drive it to the edge and bring our
+// king over. A lone king boxed in without being in check is one careless move from stalemate,
+// so that is penalised rather than rewarded.
+fn lone_king_score(board: &Board, attacks: &AttackInfo, strong: Color, params: &EvalParams) -> i32 {
+    let weak = strong.other();
+    if (board.by_color(weak) & !board.kings()).any() { return 0; }
+    if non_pawn_material(board, strong, params) < params.material_value(Role::Rook) { return 0; }
+    let (Some(weak_king), Some(strong_king)) = (board.king_of(weak), board.king_of(strong)) else { return 0; };
+
+    let edge_distance = |coord: i32| (3 - coord).max(coord - 4);
+    let centre_distance = edge_distance(weak_king.file() as i32) + edge_distance(weak_king.rank() as i32);
+    let mut score = params.mop_up_edge * centre_distance + params.mop_up_kings * (7 - weak_king.distance(strong_king) as i32);
+
+    // Squares are tested with the king lifted off the board so it can't hide behind itself,
+    // which the cached attacks (generated with it on the board) don't allow for
+    let occupied = board.occupied() ^ Bitboard::from(weak_king);
+    let in_check = attacks.by_color.get(strong).contains(weak_king);
+    let boxed = (attacks::king_attacks(weak_king) & !board.by_color(weak)).into_iter()
+        .all(|sq| board.attacks_to(sq, strong, occupied).any());
+    if boxed && !in_check {
+        score += params.stalemate_risk_penalty;
+    }
+    score
+}
+
+// Bishops want open lines, knights want a blocked centre to hop around. Closedness runs from
+// -8 (no pawns) to +8 (sixteen pawns, or fewer with head-to-head pairs counted twice).
+fn minor_piece_imbalance(board: &Board, color: Color, locked_pairs: usize, params: &EvalParams) -> i32 {
+    let closedness = (board.pawns().count() + 2 * locked_pairs).min(16) as i32 - 8;
+    let bishops = (board.bishops() & board.by_color(color)).count() as i32;
+    let knights = (board.knights() & board.by_color(color)).count() as i32;
+    (knights * params.knight_closed_bonus - bishops * params.bishop_open_bonus) * closedness / 8
+}
+
+// Safe squares each piece attacks: not occupied by our own pieces and not covered by an enemy
+// pawn. Weighted per role and blended between the middlegame and endgame weights by phase.
+fn mobility(board: &Board, attacks: &AttackInfo, color: Color, phase: i32, params: &EvalParams) -> i32 {
+    let ours = board.by_color(color);
+    let safe = !ours & !*attacks.pawns.get(color.other());
+    let mut score = 0;
+    for square in ours & !board.pawns() {
+        let Some(role) = board.role_at(square) else { continue; };
+        let count = (attacks.from(square) & safe).count() as i32;
+        let r = role as usize - 1;
+        score += count * (params.mobility_mg[r] * (256 - phase) + params.mobility_eg[r] * phase) / 256;
+    }
+    score
+}
+
+// King tropism: pieces, queens and knights above all, gathering near the enemy king. Counted
+// by king-move (Chebyshev) distance and faded out toward the endgame like connectivity.
+fn king_tropism(board: &Board, color: Color, phase: i32, params: &EvalParams) -> i32 {
+    let Some(their_king) = board.king_of(color.other()) else { return 0; };
+    let mut score = 0;
+    for square in board.by_color(color) & !board.pawns() & !board.kings() {
+        let Some(role) = board.role_at(square) else { continue; };
+        score += params.tropism_weight[role as usize - 1] * (7 - square.distance(their_king) as i32);
+    }
+    score * (256 - phase) / 256
+}
+
+// Harmonious development: minor pieces standing on pawn-defended squares, and rooks that see
+// each other along the back rank. Fades out toward the endgame.
+fn connectivity(board: &Board, attacks: &AttackInfo, color: Color, phase: i32, params: &EvalParams) -> i32 {
+    let ours = board.by_color(color);
+    let mut connections = ((board.knights() | board.bishops()) & ours & *attacks.pawns.get(color)).count() as i32;
+
+    let back_rank = Bitboard::from_rank(if color == Color::White { Rank::First } else { Rank::Eighth });
+    let rooks = board.rooks() & ours & back_rank;
+    if rooks.count() == 2 && rooks.first().is_some_and(|sq| attacks.from(sq).contains(rooks.last().unwrap())) {
+        connections += 1;
+    }
+    connections * params.connectivity_bonus * (256 - phase) / 256
+}
+
+// b1, c1, f1 and g1: where White's minors start; Black's are the same squares on rank 8
+const MINOR_HOMES: Bitboard = Bitboard(0x0000_0000_0000_0066);
+
+// Development lead in the opening: minors brought out, the king castled with its rook freed from
+// the corner, and no queen sortie while the minors are still at home. Faded out by
+// DEVELOPMENT_PHASE_END, long before the middlegame proper.
+fn development(board: &Board, color: Color, phase: i32, params: &EvalParams) -> i32 {
+    if phase >= DEVELOPMENT_PHASE_END { return 0; }
+    let ours = board.by_color(color);
+    let (back_rank, homes, queen_home) = if color == Color::White {
+        (Rank::First, MINOR_HOMES, Square::D1)
+    } else {
+        (Rank::Eighth, MINOR_HOMES.flip_vertical(), Square::D8)
+    };
+    let minors = (board.knights() | board.bishops()) & ours;
+    let mut score = (minors & !homes).count() as i32 * params.developed_minor_bonus;
+
+    if let Some(king) = board.king_of(color).filter(|k| k.rank() == back_rank && k.file() != File::E) {
+        // A rook left between the king and its corner has not been castled with
+        let corner_side: Bitboard = Bitboard::from_rank(back_rank).into_iter()
+            .filter(|sq| if king.file() > File::E { sq.file() > king.file() } else { sq.file() < king.file() })
+            .collect();
+        if (board.rooks() & ours & corner_side).is_empty() {
+            score += params.castled_bonus;
+        }
+    }
+
+    score += early_queen_sortie(board.queens() & ours, (minors & homes).count() as i32, queen_home, params);
+    score * (DEVELOPMENT_PHASE_END - phase) / DEVELOPMENT_PHASE_END
+}
+
+// The queen out while most minors still sit at home: it gets chased around for tempo while the
+// rest of the army waits. Heavier the fewer minors have come out.
+fn early_queen_sortie(queens: Bitboard, minors_at_home: i32, queen_home: Square, params: &EvalParams) -> i32 {
+    if minors_at_home < 2 || queens.is_empty() || queens.contains(queen_home) { return 0; }
+    (minors_at_home - 1) * params.early_queen_penalty
+}
+
+fn non_pawn_material(board: &Board, color: Color, params: &EvalParams) -> i32 {
+    let pieces = board.by_color(color) & !board.pawns() & !board.kings();
+    pieces.into_iter().filter_map(|sq| board.role_at(sq)).map(|role| params.material_value(role)).sum()
+}
+
+// The side to move, a rook or more ahead, has left the opponent with no legal move. Any waiting
+// move now may be stalemate, so the eval leans toward lines that leave the weaker side some air
+// until mate is forced. A lone king is left to lone_king_score, which catches it cheaply.
+fn stalemate_risk(pos: &Chess, params: &EvalParams) -> i32 {
     let board = pos.board();
-    let turn = pos.turn();
-    
+    let (strong, weak) = (pos.turn(), pos.turn().other());
+    if (board.by_color(weak) & !board.kings()).is_empty() { return 0; }
+    let material = |color| non_pawn_material(board, color, params)
+        + (board.pawns() & board.by_color(color)).count() as i32 * params.material_value(Role::Pawn);
+    if material(strong) - material(weak) < params.material_value(Role::Rook) { return 0; }
+
+    let Ok(their_turn) = pos.clone().swap_turn() else { return 0; };
+    if !their_turn.is_check() && their_turn.legal_moves().is_empty() {
+        params.stalemate_risk_penalty
+    } else {
+        0
+    }
+}
+
+// Bishop and rook pawns (all on one file) against a bare king that holds the promotion corner,
+// with the bishop unable to ever cover that corner: the king can't be driven out.
+fn is_wrong_bishop_draw(board: &Board, strong: Color) -> bool {
+    let weak = strong.other();
+    if (board.by_color(weak) & !board.kings()).any() { return false; }
+    let pieces = board.by_color(strong) & !board.kings() & !board.pawns();
+    let pawns = board.by_color(strong) & board.pawns();
+    let (Some(bishop), Some(pawn)) = (pieces.single_square().filter(|&sq| board.role_at(sq) == Some(Role::Bishop)), pawns.first()) else { return false; };
+    if pawn.file() != File::A && pawn.file() != File::H { return false; }
+    if (pawns & !Bitboard::from_file(pawn.file())).any() { return false; }
+
+    let promotion = Square::from_coords(pawn.file(), if strong == Color::White { Rank::Eighth } else { Rank::First });
+    let Some(weak_king) = board.king_of(weak) else { return false; };
+    bishop.is_light() != promotion.is_light() && weak_king.distance(promotion) <= 1
+}
+
+// Two knights and nothing else against a bare king: mate exists only if the defender blunders
+// into it, so it can't be forced.
+fn is_two_knights_draw(board: &Board, strong: Color) -> bool {
+    let weak = strong.other();
+    if (board.by_color(weak) & !board.kings()).any() { return false; }
+    let pieces = board.by_color(strong) & !board.kings();
+    pieces.count() == 2 && (pieces & !board.knights()).is_empty()
+}
+
+// A lone queen against a rook or bishop pawn one step from promoting, its king beside it: the
+// queen can't force the king in front of the pawn, since stalemate (rook pawn) or a counter-attack
+// on the queen (bishop pawn) saves the defence, unless the attacking king is close enough to help.
+fn is_queen_vs_seventh_rank_pawn_draw(board: &Board, strong: Color) -> bool {
+    let weak = strong.other();
+    let pieces = board.by_color(strong) & !board.kings();
+    if pieces.count() != 1 || (pieces & board.queens()).is_empty() { return false; }
+    let Some(pawn) = (board.by_color(weak) & !board.kings()).single_square() else { return false; };
+    if board.role_at(pawn) != Some(Role::Pawn) { return false; }
+    if ![File::A, File::C, File::F, File::H].contains(&pawn.file()) { return false; }
+    let seventh = if weak == Color::White { Rank::Seventh } else { Rank::Second };
+    if pawn.rank() != seventh { return false; }
+
+    let promotion = Square::from_coords(pawn.file(), if weak == Color::White { Rank::Eighth } else { Rank::First });
+    let (Some(weak_king), Some(strong_king)) = (board.king_of(weak), board.king_of(strong)) else { return false; };
+    board.piece_at(promotion).is_none() && weak_king.distance(pawn) <= 1 && strong_king.distance(pawn) > QUEEN_VS_PAWN_KING_DISTANCE
+}
+
+// Enough pawns head to head, none able to capture another, and a `strong` pawn on every file so
+// its rooks have no open or semi-open file to come in by: extra material there is often unconvertible.
+fn is_blockade(board: &Board, strong: Color) -> bool {
+    let white_pawns = board.pawns() & board.white();
+    let black_pawns = board.pawns() & board.black();
+    if (white_pawns.shift(8) & black_pawns).count() < FORTRESS_LOCKED_PAIRS { return false; }
+    let lever = white_pawns.into_iter().any(|sq| (attacks::pawn_attacks(Color::White, sq) & black_pawns).any());
+    if lever { return false; }
+    let strong_pawns = board.pawns() & board.by_color(strong);
+    File::ALL.iter().all(|&f| (strong_pawns & Bitboard::from_file(f)).any())
+}
+
+// How winnable the material balance is, out of SCALE_NORMAL. Applied to the endgame share of the score.
+pub fn scale_factor(board: &Board, params: &EvalParams) -> i32 {
+    let white_npm = non_pawn_material(board, Color::White, params);
+    let black_npm = non_pawn_material(board, Color::Black, params);
+    let pawn_value = params.material_value(Role::Pawn);
+    let white_pawns = (board.pawns() & board.white()).count() as i32;
+    let black_pawns = (board.pawns() & board.black()).count() as i32;
+    let (strong, strong_npm, weak_npm, strong_pawns) = if white_npm + white_pawns * pawn_value >= black_npm + black_pawns * pawn_value {
+        (Color::White, white_npm, black_npm, white_pawns)
+    } else {
+        (Color::Black, black_npm, white_npm, black_pawns)
+    };
+
+    if is_wrong_bishop_draw(board, Color::White) || is_wrong_bishop_draw(board, Color::Black)
+        || is_two_knights_draw(board, Color::White) || is_two_knights_draw(board, Color::Black) {
+        return SCALE_DRAW;
+    }
+
+    if is_queen_vs_seventh_rank_pawn_draw(board, Color::White) || is_queen_vs_seventh_rank_pawn_draw(board, Color::Black) {
+        return SCALE_DRAWISH;
+    }
+
+    // Without pawns, being up no more than a minor piece rarely wins (KRvKB, KRBvKR, KRvKR...)
+    if strong_pawns == 0 && strong_npm - weak_npm <= params.material_value(Role::Bishop) {
+        return SCALE_DRAWISH;
+    }
+
+    if is_blockade(board, strong) {
+        return SCALE_BLOCKADE;
+    }
+
+    // Bishops of opposite colours and nothing else besides pawns
+    let white_bishops = board.bishops() & board.white();
+    let black_bishops = board.bishops() & board.black();
+    if white_npm == params.material_value(Role::Bishop) && black_npm == params.material_value(Role::Bishop)
+        && white_bishops.count() == 1 && black_bishops.count() == 1
+        && (white_bishops & Bitboard::LIGHT_SQUARES).any() != (black_bishops & Bitboard::LIGHT_SQUARES).any() {
+        return SCALE_OPPOSITE_BISHOPS;
+    }
+
+    SCALE_NORMAL
+}
+
+// 0 with all pieces on the board, 256 with only kings and pawns. Promoted pieces can push
+// the piece count past the starting total, so it is capped there.
+fn game_phase(board: &Board) -> i32 {
     let n_knights = board.knights().count();
     let n_bishops = board.bishops().count();
     let n_rooks = board.rooks().count();
     let n_queens = board.queens().count();
     
     let total_phase = 24; 
-    let mut phase = total_phase - (n_knights + n_bishops + n_rooks * 2 + n_queens * 4) as i32;
-    phase = (phase * 256 + (total_phase / 2)) / total_phase;
+    let pieces = ((n_knights + n_bishops + n_rooks * 2 + n_queens * 4) as i32).min(total_phase);
+    let phase = total_phase - pieces;
+    ((phase * 256 + (total_phase / 2)) / total_phase).clamp(0, 256)
+}
+
+// Material and piece-square tables only. No move generation, so mates and stalemates
+// are left to the search.
+fn evaluate_material(pos: &Chess, params: &EvalParams) -> i32 {
+    if pos.is_insufficient_material() { return 0; }
+    let board = pos.board();
+    let phase = game_phase(board);
+    let mut score = 0;
+    for (square, piece) in board.clone() {
+        let val = params.material_value(piece.role) + get_pst_value(&params.pst, piece.role, piece.color, square, phase);
+        if piece.color == pos.turn() { score += val; } else { score -= val; }
+    }
+    score
+}
+
+pub fn evaluate(pos: &Chess, params: &EvalParams) -> i32 {
+    match params.kind {
+        EvalKind::HandCrafted => evaluate_hand_crafted(pos, params),
+        EvalKind::Nnue => evaluate_nnue(pos, params),
+    }
+}
+
+// evaluate is from the side to move; seen from White, the colour-flipped mirror (turn swapped
+// too) must come out exactly negated. Debug builds check this at the root of every search.
+pub fn evaluate_is_symmetric(pos: &Chess, params: &EvalParams) -> bool {
+    let white_view = |pos: &Chess| if pos.turn() == Color::White { evaluate(pos, params) } else { -evaluate(pos, params) };
+    let Ok(mirror) = pos.clone().into_setup(EnPassantMode::Legal).into_mirrored().position::<Chess>(CastlingMode::Standard) else { return true; };
+    white_view(&mirror) == -white_view(pos)
+}
+
+// Stand-in until there are weights to load
+fn evaluate_nnue(pos: &Chess, params: &EvalParams) -> i32 {
+    evaluate_hand_crafted(pos, params)
+}
+
+fn evaluate_hand_crafted(pos: &Chess, params: &EvalParams) -> i32 {
+    if params.mode == EvalMode::Material { return evaluate_material(pos, params); }
+
+    if pos.legal_moves().is_empty() {
+        if pos.is_check() { return -30000; }
+        return 0;
+    }
+    if pos.is_insufficient_material() { return 0; }
+
+    let board = pos.board();
+    let turn = pos.turn();
+    let phase = game_phase(board);
+    let attacks = AttackInfo::new(board);
 
     let mut score = 0;
     
@@ -45,74 +624,65 @@ pub fn evaluate(pos: &Chess) -> i32 {
 
     for square in board.occupied() {
         if let Some(piece) = board.piece_at(square) {
-            let mut val = get_material_value(piece.role);
-            val += get_pst_value(piece.role, piece.color, square, phase);
+            let mut val = params.material_value(piece.role);
+            val += get_pst_value(&params.pst, piece.role, piece.color, square, phase);
 
             match piece.role {
                 Role::Rook => {
-                    let file_bb = Bitboard::from_file(square.file());
-                    if (board.pawns() & file_bb).is_empty() {
-                        val += ROOK_ON_OPEN_FILE;
-                    }
+                    val += rook_file_score(board, &attacks, square, piece.color, params);
                     // Rook on 7th rank
                     let seventh = if piece.color == Color::White { Rank::Seventh } else { Rank::Second };
                     if square.rank() == seventh {
-                        val += ROOK_ON_7TH_BONUS;
+                        val += params.rook_on_7th_bonus;
+                    }
+                    if rook_behind_passer(board, square) {
+                        val += params.rook_behind_passer;
                     }
                 },
                 Role::Knight => {
-                    // Knight outpost
-                    let rank_idx = square.rank() as i32;
-                    let is_central_file = square.file() as i32 >= 2 && square.file() as i32 <= 5;
-                    let is_advanced = if piece.color == Color::White { rank_idx >= 3 } else { rank_idx <= 4 };
-                    if is_central_file && is_advanced {
-                        let adj_files = square.file().offset(-1).map_or(Bitboard(0), Bitboard::from_file) |
-                                        square.file().offset(1).map_or(Bitboard(0), Bitboard::from_file);
-                        let is_defended = !(board.pawns() & board.by_color(piece.color) & adj_files).is_empty();
-                        if is_defended {
-                            val += KNIGHT_OUTPOST_BONUS;
-                        }
+                    if square.file() == File::A || square.file() == File::H {
+                        val += params.knight_rim_penalty;
+                    }
+
+                    if is_outpost(square, piece.color, board) {
+                        val += params.knight_outpost_bonus;
                     }
                 },
-                Role::King => {
-                    if phase < 128 {
-                        let shield_rank = if piece.color == Color::White { Rank::Second } else { Rank::Seventh };
-                        let shield_mask = Bitboard::from_rank(shield_rank) & 
-                                          (Bitboard::from_file(square.file()) | 
-                                           square.file().offset(-1).map_or(Bitboard(0), Bitboard::from_file) |
-                                           square.file().offset(1).map_or(Bitboard(0), Bitboard::from_file));
-                        let shield_count = (board.pawns() & board.by_color(piece.color) & shield_mask).count();
-                        val += shield_count as i32 * KING_SHIELD_BONUS;
+                Role::Bishop => {
+                    let my_pawns = if piece.color == Color::White { white_pawns } else { black_pawns };
+                    let square_colour = if square.is_light() { Bitboard::LIGHT_SQUARES } else { Bitboard::DARK_SQUARES };
+                    val += (my_pawns & square_colour).count() as i32 * params.bad_bishop_penalty;
+
+                    if is_outpost(square, piece.color, board) {
+                        val += params.bishop_outpost_bonus;
                     }
                 },
+                Role::King if phase < 128 => {
+                    let shield_rank = if piece.color == Color::White { Rank::Second } else { Rank::Seventh };
+                    let shield_mask = Bitboard::from_rank(shield_rank) &
+                                      (Bitboard::from_file(square.file()) | adjacent_files(square.file()));
+                    let shield_count = (board.pawns() & board.by_color(piece.color) & shield_mask).count();
+                    val += shield_count as i32 * params.king_shield_bonus;
+                    val += king_file_exposure(board, piece.color, params);
+                    val += fianchetto_score(board, piece.color, square, params);
+                },
                 Role::Pawn => {
                     let color = piece.color;
                     let my_pawns = if color == Color::White { white_pawns } else { black_pawns };
                     let enemy_pawns = if color == Color::White { black_pawns } else { white_pawns };
 
                     if (my_pawns & Bitboard::from_file(square.file())).count() > 1 {
-                        val += DOUBLED_PAWN_PENALTY;
+                        val += doubled_pawn_score(board, square, color, params);
                     }
 
-                    let adj_files = square.file().offset(-1).map_or(Bitboard(0), Bitboard::from_file) |
-                                    square.file().offset(1).map_or(Bitboard(0), Bitboard::from_file);
-                    if (my_pawns & adj_files).is_empty() {
-                        val += ISOLATED_PAWN_PENALTY;
+                    if is_isolated(square, my_pawns) {
+                        val += params.isolated_pawn_penalty;
                     }
 
-                    let mut ahead = Bitboard(0);
-                    if color == Color::White {
-                        for r in (square.rank() as i8 + 1)..8 {
-                            ahead |= Bitboard::from_rank(Rank::new(r as u32));
-                        }
-                    } else {
-                        for r in 0..(square.rank() as i8) {
-                            ahead |= Bitboard::from_rank(Rank::new(r as u32));
-                        }
-                    }
-                    if (enemy_pawns & (Bitboard::from_file(square.file()) | adj_files) & ahead).is_empty() {
+                    if is_passed(square, color, enemy_pawns) {
                         let rel_rank = if color == Color::White { square.rank() as usize } else { 7 - square.rank() as usize };
-                        val += PASSED_PAWN_BONUS[rel_rank];
+                        val += params.passed_pawn_bonus[rel_rank];
+                        val += blockade_penalty(board, square, color, params);
                     }
                 },
                 _ => {}
@@ -126,19 +696,579 @@ pub fn evaluate(pos: &Chess) -> i32 {
         }
     }
 
+    // The bishop pair is worth less once the pawns have locked the position
+    let locked_pairs = (white_pawns.shift(8) & black_pawns).count();
+    let pair_bonus = if locked_pairs >= LOCKED_PAWN_PAIRS { params.locked_bishop_pair_bonus } else { params.bishop_pair_bonus };
+
     let white_bishops = (board.bishops() & board.white()).count();
     let black_bishops = (board.bishops() & board.black()).count();
     if turn == Color::White {
-        if white_bishops >= 2 { score += 50; }
-        if black_bishops >= 2 { score -= 50; }
+        if white_bishops >= 2 { score += pair_bonus; }
+        if black_bishops >= 2 { score -= pair_bonus; }
     } else {
-        if black_bishops >= 2 { score += 50; }
-        if white_bishops >= 2 { score -= 50; }
+        if black_bishops >= 2 { score += pair_bonus; }
+        if white_bishops >= 2 { score -= pair_bonus; }
+    }
+
+    if phase < 128 {
+        score += pawn_storm(board, turn, params) - pawn_storm(board, turn.other(), params);
+    }
+    score += pawn_majority(board, turn, params) - pawn_majority(board, turn.other(), params);
+    score += minor_piece_imbalance(board, turn, locked_pairs, params) - minor_piece_imbalance(board, turn.other(), locked_pairs, params);
+
+    score += lone_king_score(board, &attacks, turn, params) - lone_king_score(board, &attacks, turn.other(), params);
+    score += stalemate_risk(pos, params);
+
+    score += mobility(board, &attacks, turn, phase, params) - mobility(board, &attacks, turn.other(), phase, params);
+    score += connectivity(board, &attacks, turn, phase, params) - connectivity(board, &attacks, turn.other(), phase, params);
+    score += king_tropism(board, turn, phase, params) - king_tropism(board, turn.other(), phase, params);
+    score += development(board, turn, phase, params) - development(board, turn.other(), phase, params);
+    score += heavy_infiltration(board, turn, params) - heavy_infiltration(board, turn.other(), params);
+    score += pawn_chain_score(board, turn, params) - pawn_chain_score(board, turn.other(), params);
+    score += king_flight_score(board, &attacks, turn, phase, params) - king_flight_score(board, &attacks, turn.other(), phase, params);
+
+    let scale = scale_factor(board, params);
+    if scale == SCALE_DRAW { return 0; }
+    if scale < SCALE_NORMAL {
+        let effective = SCALE_NORMAL - (SCALE_NORMAL - scale) * phase / 256;
+        score = score * effective / SCALE_NORMAL;
     }
 
-    // Dynamic mobility weighting
-    let mobility = pos.legal_moves().len() as i32;
-    score += mobility / 2;
+    // Discount advantages that can't be converted before the fifty-move rule
+    let halfmoves = pos.halfmoves().min(100);
+    if halfmoves > FIFTY_MOVE_SCALE_START {
+        score = score * (100 - halfmoves) as i32 / (100 - FIFTY_MOVE_SCALE_START) as i32;
+    }
 
     score
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shakmaty::fen::Fen;
+
+    fn eval_fen(fen: &str) -> i32 {
+        let pos: Chess = fen.parse::<Fen>().unwrap().into_position(CastlingMode::Standard).unwrap();
+        evaluate(&pos, &EvalParams::default())
+    }
+
+    #[test]
+    fn rim_knight_scores_worse_than_central_knight() {
+        let rim = eval_fen("4k3/pp6/8/8/N7/8/PP6/4K3 w - - 0 1");
+        let central = eval_fen("4k3/pp6/8/8/3N4/8/PP6/4K3 w - - 0 1");
+        assert!(rim < central, "rim {} vs central {}", rim, central);
+    }
+
+    #[test]
+    fn outposts_are_mirrored_between_colours() {
+        let board = Board::from_ascii_board_fen(b"4k3/pp6/8/3N4/4P3/8/PP6/4K3").unwrap();
+        assert!(is_outpost(Square::D5, Color::White, &board));
+        let board = Board::from_ascii_board_fen(b"4k3/pp6/8/4p3/3n4/8/PP6/4K3").unwrap();
+        assert!(is_outpost(Square::D4, Color::Black, &board));
+
+        let white = eval_fen("4k3/pp6/8/3N4/4P3/8/PP6/4K3 w - - 0 1");
+        let black = eval_fen("4k3/pp6/8/4p3/3n4/8/PP6/4K3 b - - 0 1");
+        assert_eq!(white, black);
+    }
+
+    #[test]
+    fn pawn_storm_rewards_advancing_on_opposite_castled_king() {
+        // Sicilian-style: White castled long, Black short
+        let params = EvalParams::default();
+        let home = Board::from_ascii_board_fen(b"r4rk1/pp3ppp/8/8/8/8/PPP2PPP/2KR3R").unwrap();
+        let storm = Board::from_ascii_board_fen(b"r4rk1/pp3ppp/8/8/6PP/8/PPP2P2/2KR3R").unwrap();
+        assert!(pawn_storm(&storm, Color::White, &params) > pawn_storm(&home, Color::White, &params));
+
+        let same_side = Board::from_ascii_board_fen(b"r4rk1/pp3ppp/8/8/6PP/8/PPP2P2/5RK1").unwrap();
+        assert_eq!(pawn_storm(&same_side, Color::White, &params), 0);
+    }
+
+    #[test]
+    fn open_files_in_front_of_king_are_penalised() {
+        let params = EvalParams::default();
+        let covered = Board::from_ascii_board_fen(b"r5k1/ppp2ppp/8/8/8/8/PPP2PPP/6K1").unwrap();
+        let stripped = Board::from_ascii_board_fen(b"r5k1/ppp2ppp/8/8/8/8/PPP5/6K1").unwrap();
+        let facing_rook = Board::from_ascii_board_fen(b"6kr/ppp2ppp/8/8/8/8/PPP5/6K1").unwrap();
+
+        assert_eq!(king_file_exposure(&covered, Color::White, &params), 0);
+        let bare = king_file_exposure(&stripped, Color::White, &params);
+        assert!(bare < 0);
+        assert!(king_file_exposure(&facing_rook, Color::White, &params) < bare);
+    }
+
+    #[test]
+    fn boxed_in_king_is_less_safe() {
+        let params = EvalParams::default();
+        let flight = |fen: &[u8]| {
+            let board = Board::from_ascii_board_fen(fen).unwrap();
+            king_flight_score(&board, &AttackInfo::new(&board), Color::Black, 0, &params)
+        };
+        // Qd6 takes d8, d7, e7 and f8 from the e8 king, leaving only f7
+        let open = flight(b"4k3/8/8/8/8/8/8/4K3");
+        let boxed = flight(b"4k3/8/3Q4/8/8/8/8/4K3");
+        assert_eq!(open, 0);
+        assert_eq!(boxed, params.king_flight_penalty);
+        // Own pieces block flight squares too
+        assert_eq!(flight(b"3rkr2/3ppp2/8/8/8/8/8/4K3"), 2 * params.king_flight_penalty);
+    }
+
+    #[test]
+    fn knight_blockades_a_passer_better_than_a_rook() {
+        let params = EvalParams::default();
+        let knight = Board::from_ascii_board_fen(b"6k1/8/3n4/3P4/8/8/8/6K1").unwrap();
+        let rook = Board::from_ascii_board_fen(b"6k1/8/3r4/3P4/8/8/8/6K1").unwrap();
+        let free = Board::from_ascii_board_fen(b"6k1/8/8/3P4/8/8/8/6K1").unwrap();
+        let by_knight = blockade_penalty(&knight, Square::D5, Color::White, &params);
+        let by_rook = blockade_penalty(&rook, Square::D5, Color::White, &params);
+        assert_eq!(by_knight, -params.blockader_bonus[1]);
+        assert!(by_knight < by_rook, "knight {} vs rook {}", by_knight, by_rook);
+        assert_eq!(blockade_penalty(&free, Square::D5, Color::White, &params), 0);
+
+        // Black passer on d4 blockaded from d3
+        let black = Board::from_ascii_board_fen(b"6k1/8/8/8/3p4/3N4/8/6K1").unwrap();
+        assert_eq!(blockade_penalty(&black, Square::D4, Color::Black, &params), by_knight);
+    }
+
+    #[test]
+    fn rook_behind_passed_pawn_is_rewarded() {
+        // White passer on b5: a rook on b1 supports it, a rook on b8 would be in front of it
+        let behind = Board::from_ascii_board_fen(b"6k1/5ppp/8/1P6/8/8/5PPP/1R4K1").unwrap();
+        let in_front = Board::from_ascii_board_fen(b"1R4k1/5ppp/8/1P6/8/8/5PPP/6K1").unwrap();
+        assert!(rook_behind_passer(&behind, Square::B1));
+        assert!(!rook_behind_passer(&in_front, Square::B8));
+
+        // Black rook chasing the same passer from behind
+        let enemy_behind = Board::from_ascii_board_fen(b"6k1/5ppp/8/1P6/8/8/5PPP/1r4K1").unwrap();
+        assert!(rook_behind_passer(&enemy_behind, Square::B1));
+
+        let params = EvalParams::default();
+        let no_bonus = EvalParams { rook_behind_passer: 0, ..EvalParams::default() };
+        let pos = "6k1/5ppp/8/1P6/8/8/5PPP/1R4K1 w - - 0 1".parse::<Fen>().unwrap().into_position::<Chess>(CastlingMode::Standard).unwrap();
+        assert_eq!(evaluate(&pos, &params) - evaluate(&pos, &no_bonus), ROOK_BEHIND_PASSER);
+    }
+
+    #[test]
+    fn material_mode_skips_positional_terms() {
+        let material = EvalParams { mode: EvalMode::Material, ..EvalParams::default() };
+        // Same material and PSTs, but the full eval sees the doubled, isolated pawns
+        let pos: Chess = "4k3/8/8/8/8/4P3/4P3/4K3 w - - 0 1".parse::<Fen>().unwrap().into_position(CastlingMode::Standard).unwrap();
+        let full = evaluate(&pos, &EvalParams::default());
+        let fast = evaluate(&pos, &material);
+        assert!(fast > full);
+        assert!(fast > 150);
+    }
+
+    #[test]
+    fn phase_stays_in_range_with_promoted_queens() {
+        let start = Board::default();
+        let two_queens = Board::from_ascii_board_fen(b"rnbqkbnr/pppqpppp/8/8/8/8/PPPQPPPP/RNBQKBNR").unwrap();
+        let bare = Board::from_ascii_board_fen(b"4k3/pppp4/8/8/8/8/PPPP4/4K3").unwrap();
+        assert_eq!(game_phase(&start), 0);
+        assert_eq!(game_phase(&two_queens), 0);
+        assert_eq!(game_phase(&bare), 256);
+    }
+
+    #[test]
+    fn rook_pressing_isolated_pawn_scores_more() {
+        let params = EvalParams::default();
+        // Black d-pawn isolated in the first board, supported by the c-pawn in the second
+        let isolated = Board::from_ascii_board_fen(b"6k1/pp3ppp/8/3p4/8/8/PP3PPP/3R2K1").unwrap();
+        let supported = Board::from_ascii_board_fen(b"6k1/p1p2ppp/8/3p4/8/8/PP3PPP/3R2K1").unwrap();
+        let isolated_score = rook_file_score(&isolated, &AttackInfo::new(&isolated), Square::D1, Color::White, &params);
+        assert_eq!(isolated_score - rook_file_score(&supported, &AttackInfo::new(&supported), Square::D1, Color::White, &params), params.rook_attacks_weak_pawn);
+
+        // An enemy rook on the same file halves the semi-open bonus
+        let contested = Board::from_ascii_board_fen(b"3r2k1/p1p2ppp/8/3p4/8/8/PP3PPP/3R2K1").unwrap();
+        assert_eq!(rook_file_score(&contested, &AttackInfo::new(&contested), Square::D1, Color::White, &params), params.rook_on_semi_open_file / 2);
+
+        // d6 behind c5 with d5 covered by e4 is backward; with c7 still able to support it, it isn't
+        let e4 = Bitboard::from(Square::E4);
+        assert!(is_backward(Square::D6, Color::Black, Bitboard::from(Square::C5) | Bitboard::from(Square::D6), e4));
+        assert!(!is_backward(Square::D6, Color::Black, Bitboard::from(Square::C7) | Bitboard::from(Square::D6), e4));
+    }
+
+    #[test]
+    fn fianchetto_without_bishop_is_a_hole() {
+        let params = EvalParams::default();
+        let intact = Board::from_ascii_board_fen(b"6k1/5pbp/6p1/8/8/6P1/5PBP/6K1").unwrap();
+        let hole = Board::from_ascii_board_fen(b"6k1/5pbp/6p1/8/8/6P1/5P1P/6K1").unwrap();
+        assert_eq!(fianchetto_score(&intact, Color::White, Square::G1, &params), params.fianchetto_bonus);
+        assert_eq!(fianchetto_score(&hole, Color::White, Square::G1, &params), params.fianchetto_hole_penalty);
+        assert_eq!(fianchetto_score(&hole, Color::Black, Square::G8, &params), params.fianchetto_bonus);
+        assert!(params.fianchetto_bonus > params.fianchetto_hole_penalty);
+    }
+
+    #[test]
+    fn carlsbad_majority_and_minority_attack() {
+        let params = EvalParams::default();
+        // Carlsbad: Black has the queenside majority (a7 b7 c6 against a2 b2)
+        let carlsbad = Board::from_ascii_board_fen(b"r4rk1/pp3ppp/2p5/3p4/3P4/4P3/PP3PPP/R4RK1").unwrap();
+        assert_eq!(pawn_majority(&carlsbad, Color::Black, &params), params.pawn_majority_bonus);
+        assert_eq!(pawn_majority(&carlsbad, Color::White, &params), 0);
+
+        // Once b4-b5 is under way White's minority attack counts too
+        let minority = Board::from_ascii_board_fen(b"r4rk1/p4ppp/2p5/1p1p4/1P1P4/P3P3/5PPP/R4RK1").unwrap();
+        assert_eq!(pawn_majority(&minority, Color::White, &params), params.minority_attack_bonus);
+    }
+
+    #[test]
+    fn bishop_prefers_open_positions_and_knight_closed_ones() {
+        let params = EvalParams::default();
+        let imbalance = |fen: &[u8]| {
+            let board = Board::from_ascii_board_fen(fen).unwrap();
+            let locked = ((board.pawns() & board.white()).shift(8) & board.pawns() & board.black()).count();
+            minor_piece_imbalance(&board, Color::White, locked, &params) - minor_piece_imbalance(&board, Color::Black, locked, &params)
+        };
+        // White bishop against Black knight
+        let open = imbalance(b"4k3/p4n2/8/8/8/8/P3B3/4K3");
+        let closed = imbalance(b"4k3/pp3n1p/2p1p1p1/3pPp2/3P1P2/2P3P1/PP2B2P/4K3");
+        assert!(open > 0, "open {}", open);
+        assert!(closed < 0, "closed {}", closed);
+        // The same piece counts on both sides cancel out
+        assert_eq!(imbalance(b"4k3/pp3n1p/2p1pbp1/3pPp2/3P1P2/2P3P1/PP2B2P/4KN2"), 0);
+    }
+
+    #[test]
+    fn boxing_in_a_lone_king_without_check_is_penalised() {
+        let params = EvalParams::default();
+        // Qc7 takes every square from the a8 king without checking it; Qd7 leaves b8 free
+        let boxed = Board::from_ascii_board_fen(b"k7/2Q5/1K6/8/8/8/8/8").unwrap();
+        let free = Board::from_ascii_board_fen(b"k7/3Q4/1K6/8/8/8/8/8").unwrap();
+        assert_eq!(lone_king_score(&boxed, &AttackInfo::new(&boxed), Color::White, &params) - lone_king_score(&free, &AttackInfo::new(&free), Color::White, &params), params.stalemate_risk_penalty);
+        assert_eq!(lone_king_score(&boxed, &AttackInfo::new(&boxed), Color::Black, &params), 0);
+
+        let pos: Chess = "k7/3Q4/1K6/8/8/8/8/8 w - - 0 1".parse::<Fen>().unwrap().into_position(CastlingMode::Standard).unwrap();
+        let mut engine = crate::engine::Engine::new();
+        let m = engine.find_best_move(&pos, &crate::engine::SearchLimits::depth(2)).unwrap();
+        assert_ne!(m.to_uci(CastlingMode::Standard).to_string(), "d7c7");
+    }
+
+    #[test]
+    fn pst_is_symmetric_between_colours() {
+        let pst = &PstSet::default();
+        for role in Role::ALL {
+            for square in Square::ALL {
+                for phase in [0, 128, 256] {
+                    assert_eq!(get_pst_value(pst, role, Color::White, square, phase),
+                               get_pst_value(pst, role, Color::Black, square.flip_vertical(), phase),
+                               "{:?} on {} at phase {}", role, square, phase);
+                }
+            }
+        }
+        // Castled king is preferred to a central one in the middlegame, the reverse in the endgame
+        assert!(get_pst_value(pst, Role::King, Color::Black, Square::G8, 0) > get_pst_value(pst, Role::King, Color::Black, Square::E7, 0));
+        assert!(get_pst_value(pst, Role::King, Color::Black, Square::G8, 256) < get_pst_value(pst, Role::King, Color::Black, Square::E5, 256));
+    }
+
+    #[test]
+    fn custom_pawn_pst_changes_the_eval() {
+        let pos: Chess = "4k3/8/8/8/3P4/8/8/4K3 w - - 0 1".parse::<Fen>().unwrap().into_position(CastlingMode::Standard).unwrap();
+        let default = evaluate(&pos, &EvalParams::default());
+        let table = "pawn_mg = ".to_string() + &"50 ".repeat(64) + "\npawn_eg = " + &"50 ".repeat(64);
+        let custom = EvalParams { pst: PstSet::parse(&table).unwrap(), ..EvalParams::default() };
+        let d4 = get_pst_value(&PstSet::default(), Role::Pawn, Color::White, Square::D4, game_phase(pos.board()));
+        assert_eq!(evaluate(&pos, &custom) - default, 50 - d4);
+    }
+
+    #[test]
+    fn queen_value_follows_piece_values() {
+        let pos: Chess = "3qk3/pppp4/8/8/8/8/PPPP4/3QK2Q w - - 0 1".parse::<Fen>().unwrap().into_position(CastlingMode::Standard).unwrap();
+        let default = evaluate(&pos, &EvalParams::default());
+        let heavy = EvalParams { piece_values: [100, 320, 330, 500, 1000], ..EvalParams::default() };
+        assert_eq!(evaluate(&pos, &heavy) - default, 100);
+
+        let material = EvalParams { mode: EvalMode::Material, ..heavy };
+        assert_eq!(evaluate(&pos, &material) - evaluate(&pos, &EvalParams { mode: EvalMode::Material, ..EvalParams::default() }), 100);
+    }
+
+    #[test]
+    fn drawish_material_is_scaled_down() {
+        let params = EvalParams::default();
+        let board = |fen: &str| Board::from_ascii_board_fen(fen.as_bytes()).unwrap();
+        assert_eq!(scale_factor(&board("4k3/8/8/8/8/8/8/R3K3"), &params), SCALE_NORMAL);
+        assert_eq!(scale_factor(&board("4k3/8/8/2b5/8/8/8/R3K3"), &params), SCALE_DRAWISH);
+        assert_eq!(scale_factor(&board("4k3/4r3/8/8/8/8/8/R2BK3"), &params), SCALE_DRAWISH);
+        assert_eq!(scale_factor(&board("4k3/5pp1/3b4/8/8/3B4/5PPP/6K1"), &params), SCALE_OPPOSITE_BISHOPS);
+        assert_eq!(scale_factor(&board("4k3/5pp1/4b3/8/8/3B4/5PPP/6K1"), &params), SCALE_NORMAL);
+
+        let rook_vs_bishop = eval_fen("4k3/8/8/2b5/8/8/8/R3K3 w - - 0 1");
+        assert!(rook_vs_bishop.abs() < 60, "KRvKB scored {}", rook_vs_bishop);
+    }
+
+    #[test]
+    fn locked_pawns_without_open_files_damp_the_score() {
+        let params = EvalParams::default();
+        let board = |fen: &str| Board::from_ascii_board_fen(fen.as_bytes()).unwrap();
+        // Full-width chain with White a rook up: nothing to break with and nowhere for the rook to go
+        let fortress = "4k3/8/1p1p1p1p/pPpPpPpP/P1P1P1P1/8/8/R3K3";
+        assert_eq!(scale_factor(&board(fortress), &params), SCALE_BLOCKADE);
+        // Half the pawns locked is enough while White's pawns still close every file
+        assert_eq!(scale_factor(&board("4k3/8/8/p1p1p1p1/P1P1P1P1/1P1P1P1P/8/R3K3"), &params), SCALE_BLOCKADE);
+        assert_eq!(scale_factor(&board("4k3/8/8/p1p1p3/P1P1P3/1P1P1PPP/8/R3K3"), &params), SCALE_NORMAL);
+        // Take out the a-pawns and the a-file is open
+        let open = "4k3/8/1p1p1p1p/1PpPpPpP/2P1P1P1/8/8/R3K3";
+        assert_eq!(scale_factor(&board(open), &params), SCALE_NORMAL);
+        // Without White's g-pawn the rook has a semi-open file
+        assert_eq!(scale_factor(&board("4k3/8/1p1p1p1p/pPpPpPpP/P1P1P3/8/8/R3K3"), &params), SCALE_NORMAL);
+        // c6 can be taken: a lever
+        assert_eq!(scale_factor(&board("4k3/8/1ppp1p1p/pPpPpPpP/P1P1P1P1/8/8/R3K3"), &params), SCALE_NORMAL);
+
+        let damped = eval_fen(&format!("{} w - - 0 1", fortress));
+        assert!(damped > 0 && damped < 400, "fortress scored {}", damped);
+    }
+
+    #[test]
+    fn isolated_doubled_pawns_are_worse_than_doubles_behind_an_open_file() {
+        let params = EvalParams::default();
+        // exd4-style doubling toward the centre with the rook on the e-file it opened
+        let useful = Board::from_ascii_board_fen(b"4k3/pp3ppp/8/8/3P4/2PP4/PP3PPP/4R1K1").unwrap();
+        // a-pawns doubled with nothing on the b-file
+        let ugly = Board::from_ascii_board_fen(b"4k3/pp3ppp/8/8/P7/P7/3PPPPP/4R1K1").unwrap();
+        let useful_score = doubled_pawn_score(&useful, Square::D3, Color::White, &params);
+        let ugly_score = doubled_pawn_score(&ugly, Square::A3, Color::White, &params);
+        assert!(useful_score > ugly_score, "{} vs {}", useful_score, ugly_score);
+        assert_eq!(useful_score, params.doubled_pawn_penalty[3] + params.doubled_pawn_rook_compensation);
+        assert_eq!(ugly_score, params.doubled_pawn_penalty[0] + params.isolated_doubled_penalty);
+    }
+
+    #[test]
+    fn cached_attacks_match_shakmaty() {
+        let board = Board::from_ascii_board_fen(b"r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N2N2/PP2BPPP/R2QKB1R").unwrap();
+        let attacks = AttackInfo::new(&board);
+        for color in Color::ALL {
+            let expected = board.by_color(color).into_iter().fold(Bitboard(0), |bb, sq| bb | board.attacks_from(sq));
+            assert_eq!(*attacks.by_color.get(color), expected, "{:?}", color);
+        }
+    }
+
+    #[test]
+    fn coordinated_pieces_earn_connectivity() {
+        let params = EvalParams::default();
+        let score = |fen: &[u8]| {
+            let board = Board::from_ascii_board_fen(fen).unwrap();
+            connectivity(&board, &AttackInfo::new(&board), Color::White, 0, &params)
+        };
+        // Nf3 and Bd3 on pawn-guarded squares (g2, c2), rooks on d1 and f1 seeing each other
+        let coordinated = score(b"4k3/8/8/8/8/3BPN2/PPPP1PPP/3R1RK1");
+        // Same pieces on undefended squares, with a knight between the rooks
+        let scattered = score(b"4k3/8/1B6/7N/8/8/PP3PPP/3RNRK1");
+        assert_eq!(coordinated, 3 * params.connectivity_bonus);
+        assert!(scattered < coordinated, "{} vs {}", scattered, coordinated);
+
+        let board = Board::from_ascii_board_fen(b"4k3/8/8/8/8/3BPN2/PPPP1PPP/3R1RK1").unwrap();
+        assert_eq!(connectivity(&board, &AttackInfo::new(&board), Color::White, 256, &params), 0);
+    }
+
+    #[test]
+    fn queen_near_the_enemy_king_scores_higher() {
+        let params = EvalParams::default();
+        let far = Board::from_ascii_board_fen(b"6k1/5ppp/8/8/8/8/5PPP/Q5K1").unwrap();
+        let near = Board::from_ascii_board_fen(b"6k1/5ppp/8/6Q1/8/8/5PPP/6K1").unwrap();
+        assert_eq!(king_tropism(&far, Color::White, 0, &params), 0);
+        assert_eq!(king_tropism(&near, Color::White, 0, &params), 4 * params.tropism_weight[4]);
+        assert_eq!(king_tropism(&near, Color::White, 256, &params), 0);
+
+        let far_eval = eval_fen("6k1/5ppp/8/8/8/8/5PPP/Q5K1 w - - 0 1");
+        let near_eval = eval_fen("6k1/5ppp/8/6Q1/8/8/5PPP/6K1 w - - 0 1");
+        assert!(near_eval > far_eval, "near {} vs far {}", near_eval, far_eval);
+    }
+
+    #[test]
+    fn king_mobility_counts_only_in_the_endgame() {
+        let params = EvalParams::default();
+        let kings = Board::from_ascii_board_fen(b"4k3/8/8/8/3K4/8/8/8").unwrap();
+        assert_eq!(mobility(&kings, &AttackInfo::new(&kings), Color::White, 0, &params), 0);
+        assert_eq!(mobility(&kings, &AttackInfo::new(&kings), Color::White, 256, &params), 8 * params.mobility_eg[5]);
+
+        // A centralised rook is worth more moves in the endgame too
+        let rook = Board::from_ascii_board_fen(b"4k3/8/8/8/3R4/8/8/K7").unwrap();
+        assert!(mobility(&rook, &AttackInfo::new(&rook), Color::White, 256, &params) > mobility(&rook, &AttackInfo::new(&rook), Color::White, 0, &params));
+    }
+
+    #[test]
+    fn squares_covered_by_enemy_pawns_give_no_mobility() {
+        let params = EvalParams::default();
+        // Na1 only reaches b3 and c2, taken by the a4 and d3 pawns
+        let covered = Board::from_ascii_board_fen(b"4k3/8/8/8/p7/3p4/8/N3K3").unwrap();
+        let free = Board::from_ascii_board_fen(b"4k3/8/8/8/8/8/8/N3K3").unwrap();
+        assert_eq!(mobility(&covered, &AttackInfo::new(&covered), Color::White, 0, &params), 0);
+        assert_eq!(mobility(&free, &AttackInfo::new(&free), Color::White, 0, &params), 2 * params.mobility_mg[1]);
+    }
+
+    #[test]
+    fn wrong_bishop_rook_pawn_is_a_draw() {
+        let params = EvalParams::default();
+        let board = |fen: &str| Board::from_ascii_board_fen(fen.as_bytes()).unwrap();
+        // h8 is a dark square: the light-squared bishop can never drive the king out of the corner
+        assert_eq!(scale_factor(&board("7k/8/8/4K2P/8/8/4B3/8"), &params), SCALE_DRAW);
+        assert_eq!(eval_fen("7k/8/8/4K2P/8/8/4B3/8 w - - 0 1"), 0);
+        assert_eq!(scale_factor(&board("8/3b4/8/8/p2k4/8/8/1K6"), &params), SCALE_DRAW);
+
+        // Right bishop, or the king too far from the corner
+        assert_eq!(scale_factor(&board("7k/8/8/4K2P/8/8/3B4/8"), &params), SCALE_NORMAL);
+        assert_eq!(scale_factor(&board("8/8/3k4/4K2P/8/8/4B3/8"), &params), SCALE_NORMAL);
+        assert!(eval_fen("7k/8/8/4K2P/8/8/3B4/8 w - - 0 1") > 200);
+    }
+
+    #[test]
+    fn queen_against_a_supported_seventh_rank_pawn_is_drawish() {
+        let params = EvalParams::default();
+        let board = |fen: &str| Board::from_ascii_board_fen(fen.as_bytes()).unwrap();
+        // Rook and bishop pawns, either colour
+        assert_eq!(scale_factor(&board("8/8/8/8/3Q4/8/p7/1k4K1"), &params), SCALE_DRAWISH);
+        assert_eq!(scale_factor(&board("8/8/8/8/4Q3/8/2p5/1k4K1"), &params), SCALE_DRAWISH);
+        assert_eq!(scale_factor(&board("6K1/5P2/8/8/3q4/8/8/1k6"), &params), SCALE_DRAWISH);
+
+        // Centre and knight pawns lose, as does the pawn with the attacking king near, its own king
+        // away, one rank further back, or the queen already on the promotion square
+        assert_eq!(scale_factor(&board("8/8/8/8/3Q4/8/3p4/2k3K1"), &params), SCALE_NORMAL);
+        assert_eq!(scale_factor(&board("8/8/8/8/3Q4/8/1p6/2k3K1"), &params), SCALE_NORMAL);
+        assert_eq!(scale_factor(&board("8/8/8/8/3Q4/1K6/2p5/1k6"), &params), SCALE_NORMAL);
+        assert_eq!(scale_factor(&board("8/8/8/8/3Q4/8/2p5/4k1K1"), &params), SCALE_NORMAL);
+        assert_eq!(scale_factor(&board("8/8/8/8/3Q4/2p5/8/1k4K1"), &params), SCALE_NORMAL);
+        assert_eq!(scale_factor(&board("8/8/8/8/8/8/2p5/1kQ3K1"), &params), SCALE_NORMAL);
+
+        let queen_side = eval_fen("8/8/8/8/4Q3/8/2p5/1k4K1 w - - 0 1");
+        assert!(queen_side > 0 && queen_side < 200, "KQvKP on the 7th scored {}", queen_side);
+    }
+
+    #[test]
+    fn two_knights_against_a_bare_king_is_a_draw() {
+        let params = EvalParams::default();
+        let board = |fen: &str| Board::from_ascii_board_fen(fen.as_bytes()).unwrap();
+        assert_eq!(scale_factor(&board("4k3/8/8/8/8/8/8/1NN1K3"), &params), SCALE_DRAW);
+        assert_eq!(scale_factor(&board("1nn1k3/8/8/8/8/8/8/4K3"), &params), SCALE_DRAW);
+        assert_eq!(eval_fen("4k3/8/8/8/8/8/8/1NN1K3 w - - 0 1"), 0);
+
+        // With a pawn to play against, or a third piece, it is still a win to try for
+        assert_eq!(scale_factor(&board("4k3/p7/8/8/8/8/8/1NN1K3"), &params), SCALE_NORMAL);
+        assert_eq!(scale_factor(&board("4k3/8/8/8/8/8/8/1NNBK3"), &params), SCALE_NORMAL);
+    }
+
+    #[test]
+    fn development_lead_outscores_equal_material() {
+        let params = EvalParams::default();
+        // White has castled with a knight and bishop out; Black has only pushed the e-pawn
+        let board = &Board::from_ascii_board_fen(b"rnbqkbnr/pppp1ppp/8/4p3/2B1P3/5N2/PPPP1PPP/RNBQ1RK1").unwrap();
+        assert_eq!(development(board, Color::White, 0, &params), 2 * params.developed_minor_bonus + params.castled_bonus);
+        assert_eq!(development(board, Color::Black, 0, &params), 0);
+        assert!(eval_fen("rnbqkbnr/pppp1ppp/8/4p3/2B1P3/5N2/PPPP1PPP/RNBQ1RK1 w kq - 5 4") > 0);
+
+        // The queen out before the minors, and a king on g1 with the rook still on h1
+        let sortie = Board::from_ascii_board_fen(b"rnb1kbnr/pppp1ppp/8/4p2q/4P3/8/PPPP1PPP/RNBQK1NR").unwrap();
+        assert_eq!(development(&sortie, Color::Black, 0, &params), 3 * params.early_queen_penalty);
+        let uncastled = Board::from_ascii_board_fen(b"4k3/8/8/8/8/8/8/R5KR").unwrap();
+        assert_eq!(development(&uncastled, Color::White, 0, &params), 0);
+
+        // Gone once the game is past the opening
+        assert_eq!(development(board, Color::White, DEVELOPMENT_PHASE_END, &params), 0);
+    }
+
+    #[test]
+    fn rook_and_queen_on_the_seventh_outweigh_a_lone_rook() {
+        let params = EvalParams::default();
+        let board = |fen: &str| Board::from_ascii_board_fen(fen.as_bytes()).unwrap();
+        let lone_rook = heavy_infiltration(&board("6k1/1R6/8/8/8/8/8/6K1"), Color::White, &params);
+        let doubled = heavy_infiltration(&board("6k1/1R1Q4/8/8/8/8/8/6K1"), Color::White, &params);
+        assert_eq!(lone_rook, params.heavy_infiltration_bonus * params.confined_king_factor);
+        assert!(doubled > 3 * lone_rook, "{} vs {}", doubled, lone_rook);
+
+        // Less when the king has already left its back ranks, and mirrored for Black
+        let escaped = heavy_infiltration(&board("8/1R1Q4/6k1/8/8/8/8/6K1"), Color::White, &params);
+        assert_eq!(escaped * params.confined_king_factor, doubled);
+        assert_eq!(heavy_infiltration(&board("6k1/8/8/8/8/8/1r1q4/6K1"), Color::Black, &params), doubled);
+
+        // The factor is tunable from an eval file like the bonus itself
+        let tuned = EvalParams::parse("confined_king_factor = 3").unwrap();
+        assert_eq!(heavy_infiltration(&board("6k1/1R6/8/8/8/8/8/6K1"), Color::White, &tuned), tuned.heavy_infiltration_bonus * 3);
+    }
+
+    #[test]
+    fn french_chain_base_is_the_target() {
+        let params = EvalParams::default();
+        let board = |fen: &str| Board::from_ascii_board_fen(fen.as_bytes()).unwrap();
+        // 1.e4 e6 2.d4 d5 3.e5 c5: d4 carries e5, and f7 carries e6-d5
+        let french = board("rnbqkbnr/pp3ppp/4p3/2ppP3/3P4/8/PPP2PPP/RNBQKBNR");
+        assert_eq!(chain_bases(&french, Color::White), Bitboard::from(Square::D4));
+        assert_eq!(chain_bases(&french, Color::Black), Bitboard::from(Square::F7));
+
+        // With c3 in, b2 carries the whole chain; with d5 and e6 gone nothing is blocked and there is no base
+        assert_eq!(chain_bases(&board("rnbqkbnr/pp3ppp/4p3/2ppP3/3P4/2P5/PP3PPP/RNBQKBNR"), Color::White), Bitboard::from(Square::B2));
+        assert_eq!(chain_bases(&board("rnbqkbnr/pp3ppp/8/2p1P3/3P4/8/PPP2PPP/RNBQKBNR"), Color::White), Bitboard(0));
+
+        // c5xd4/d4xc5 tension with equal space: nobody is credited; push White's h-pawn and White is
+        assert_eq!(pawn_chain_score(&french, Color::White, &params), params.chain_base_penalty);
+        let more_space = board("rnbqkbnr/pp3ppp/4p3/2ppP3/3P3P/8/PPP2PP1/RNBQKBNR");
+        assert_eq!(pawn_chain_score(&more_space, Color::White, &params), params.chain_base_penalty + params.pawn_tension_bonus);
+        assert_eq!(pawn_chain_score(&more_space, Color::Black, &params), params.chain_base_penalty);
+    }
+
+    #[test]
+    fn boxing_in_a_side_with_pawns_risks_stalemate() {
+        let params = EvalParams::default();
+        let pos = |fen: &str| fen.parse::<Fen>().unwrap().into_position::<Chess>(CastlingMode::Standard).unwrap();
+        // The queen on g1 takes g8; h7 is blocked, so Black could not move at all
+        assert_eq!(stalemate_risk(&pos("7k/7p/7P/8/8/8/8/K5Q1 w - - 0 1"), &params), params.stalemate_risk_penalty);
+        assert_eq!(stalemate_risk(&pos("7k/7p/7P/8/8/8/K7/1Q6 w - - 0 1"), &params), 0);
+        // Not for the side that is behind, nor when only a lone king is left
+        assert_eq!(stalemate_risk(&pos("K6k/7p/7P/8/8/8/8/6q1 w - - 0 1"), &params), 0);
+        assert_eq!(stalemate_risk(&pos("7k/8/6QK/8/8/8/8/8 w - - 0 1"), &params), 0);
+    }
+
+    #[test]
+    fn early_queen_sortie_scores_below_normal_development() {
+        let params = EvalParams::default();
+        // 1.e4 e5 2.Qh5 Nc6 against 1.e4 e5 2.Nf3 Nc6
+        let sortie = "r1bqkbnr/pppp1ppp/2n5/4p2Q/4P3/8/PPPP1PPP/RNB1KBNR w KQkq - 2 3";
+        let developed = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3";
+        assert!(eval_fen(sortie) < eval_fen(developed), "{} vs {}", eval_fen(sortie), eval_fen(developed));
+
+        let queen_out = Bitboard::from(Square::H5);
+        assert_eq!(early_queen_sortie(queen_out, 4, Square::D1, &params), 3 * params.early_queen_penalty);
+        assert_eq!(early_queen_sortie(queen_out, 2, Square::D1, &params), params.early_queen_penalty);
+        assert_eq!(early_queen_sortie(queen_out, 1, Square::D1, &params), 0);
+        assert_eq!(early_queen_sortie(Bitboard::from(Square::D1), 4, Square::D1, &params), 0);
+    }
+
+    #[test]
+    fn evaluation_is_colour_symmetric() {
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+            "rnbqkbnr/pp3ppp/4p3/2ppP3/3P4/8/PPP2PPP/RNBQKBNR w KQkq - 0 4",
+            "rnbqkbnr/pppp1ppp/8/4p3/2B1P3/5N2/PPPP1PPP/RNBQ1RK1 b kq - 5 4",
+            "r1bqkbnr/pppp1ppp/2n5/4p2Q/4P3/8/PPPP1PPP/RNB1KBNR w KQkq - 2 3",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N2N2/PP2BPPP/R2QKB1R w KQ - 0 8",
+            "r2q1rk1/1b1nbppp/pp1ppn2/8/2PNP3/1PN1B3/P3BPPP/R2Q1RK1 w - - 0 11",
+            "2r2rk1/pp1bqppp/2n1pn2/3p4/3P4/2PBPN2/P1Q2PPP/R4RK1 b - - 0 14",
+            "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "6k1/5p2/6p1/8/7p/8/6PP/6K1 b - - 0 1",
+            "8/8/3k4/8/2PK4/8/8/8 w - - 0 1",
+            "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "6k1/1R1Q4/8/8/8/8/8/6K1 w - - 0 1",
+            "7k/7p/7P/8/8/8/8/K5Q1 w - - 0 1",
+            "4k3/8/8/8/8/8/8/3QK3 w - - 90 60",
+            "7k/8/8/4K2P/8/8/4B3/8 w - - 0 1",
+            "4k3/8/8/8/8/8/8/1NN1K3 b - - 0 1",
+            "r1b1k2r/ppppqppp/2n2n2/2b1p3/2B1P3/2NP1N2/PPP1QPPP/R1B1K2R w KQkq - 0 1",
+            "2kr3r/ppp2ppp/2n5/2b1p3/4P1b1/2NP1N2/PPP2PPP/R1B2RK1 w - - 0 10",
+            "8/5pk1/6p1/3B4/8/6P1/5PK1/2b5 w - - 0 40",
+            "r5k1/5ppp/8/8/8/8/5PPP/R5K1 b - - 0 30",
+        ];
+        let broken: Vec<&str> = fens.iter().copied().filter(|fen| !evaluate_is_symmetric(&fen.parse::<Fen>().unwrap()
+            .into_position(CastlingMode::Standard).unwrap(), &EvalParams::default())).collect();
+        assert!(broken.is_empty(), "asymmetric: {:?}", broken);
+    }
+
+    #[test]
+    fn winning_score_shrinks_near_fifty_move_limit() {
+        let fresh = eval_fen("4k3/8/8/8/8/8/8/3QK3 w - - 0 60");
+        let stale = eval_fen("4k3/8/8/8/8/8/8/3QK3 w - - 90 60");
+        assert!(fresh > 500);
+        assert!(stale > 0 && stale < fresh / 4, "fresh {} vs stale {}", fresh, stale);
+    }
+}
@@ -0,0 +1,108 @@
+use shakmaty::{CastlingSide, Chess, Color, EnPassantMode, Move, Piece, Position, Role, Square};
+use shakmaty::zobrist::{Zobrist64, ZobristHash, ZobristValue};
+
+// Incremental Zobrist hashing: same keys as `zobrist_hash::<Zobrist64>(EnPassantMode::Always)`
+// but updated from the move instead of rescanning the board at every node.
+
+pub fn full_hash(pos: &Chess) -> u64 {
+    pos.zobrist_hash::<Zobrist64>(EnPassantMode::Always).0
+}
+
+fn piece_key(square: Square, piece: Piece) -> u64 {
+    Zobrist64::zobrist_for_piece(square, piece).0
+}
+
+// Turn, castling rights and en passant: cheap to diff directly between the two positions
+fn state_delta(before: &Chess, after: &Chess) -> u64 {
+    let mut delta = Zobrist64::zobrist_for_white_turn().0;
+
+    let (old, new) = (before.castles(), after.castles());
+    for color in Color::ALL {
+        for side in CastlingSide::ALL {
+            if old.has(color, side) != new.has(color, side) {
+                delta ^= Zobrist64::zobrist_for_castling_right(color, side).0;
+            }
+        }
+    }
+
+    if let Some(sq) = before.ep_square(EnPassantMode::Always) {
+        delta ^= Zobrist64::zobrist_for_en_passant_file(sq.file()).0;
+    }
+    if let Some(sq) = after.ep_square(EnPassantMode::Always) {
+        delta ^= Zobrist64::zobrist_for_en_passant_file(sq.file()).0;
+    }
+    delta
+}
+
+// `next` must be `pos` with `m` played.
+pub fn hash_after(pos: &Chess, hash: u64, m: &Move, next: &Chess) -> u64 {
+    let us = pos.turn();
+    let mut h = hash ^ state_delta(pos, next);
+
+    match *m {
+        Move::Normal { role, from, capture, to, promotion } => {
+            h ^= piece_key(from, role.of(us));
+            h ^= piece_key(to, promotion.unwrap_or(role).of(us));
+            if let Some(captured) = capture {
+                h ^= piece_key(to, captured.of(us.other()));
+            }
+        }
+        Move::EnPassant { from, to } => {
+            h ^= piece_key(from, Role::Pawn.of(us));
+            h ^= piece_key(to, Role::Pawn.of(us));
+            h ^= piece_key(Square::from_coords(to.file(), from.rank()), Role::Pawn.of(us.other()));
+        }
+        Move::Castle { king, rook } => {
+            let side = if rook < king { CastlingSide::QueenSide } else { CastlingSide::KingSide };
+            h ^= piece_key(king, Role::King.of(us));
+            h ^= piece_key(rook, Role::Rook.of(us));
+            h ^= piece_key(side.king_to(us), Role::King.of(us));
+            h ^= piece_key(side.rook_to(us), Role::Rook.of(us));
+        }
+        Move::Put { role, to } => {
+            h ^= piece_key(to, role.of(us));
+        }
+    }
+    h
+}
+
+// Null move: only the side to move and the en passant square change.
+pub fn hash_after_null(pos: &Chess, hash: u64, next: &Chess) -> u64 {
+    hash ^ state_delta(pos, next)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shakmaty::CastlingMode;
+    use shakmaty::fen::Fen;
+
+    fn walk(pos: &Chess, hash: u64, depth: u32) -> u64 {
+        assert_eq!(hash, full_hash(pos), "incremental hash diverged at {}", Fen::from_position(pos.clone(), EnPassantMode::Legal));
+        if depth == 0 { return 1; }
+
+        let mut nodes = 0;
+        for m in pos.legal_moves() {
+            let mut next = pos.clone();
+            next.play_unchecked(&m);
+            nodes += walk(&next, hash_after(pos, hash, &m, &next), depth - 1);
+        }
+        if let Ok(next) = pos.clone().swap_turn() {
+            walk(&next, hash_after_null(pos, hash, &next), 0);
+        }
+        nodes
+    }
+
+    #[test]
+    fn incremental_hash_matches_full_hash() {
+        // Start position, Kiwipete (castling, en passant) and a promotion-heavy position
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+        ] {
+            let pos: Chess = fen.parse::<Fen>().unwrap().into_position(CastlingMode::Standard).unwrap();
+            assert!(walk(&pos, full_hash(&pos), 3) > 0);
+        }
+    }
+}
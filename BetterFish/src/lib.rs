@@ -0,0 +1,14 @@
+// The engine as a library, for embedders and match drivers that run games without going through
+// UCI strings. The BetterFish binary is a UCI front end on top of it.
+pub mod bench;
+pub mod constants;
+pub mod engine;
+pub mod evaluation;
+pub mod hash;
+pub mod opening_book;
+pub mod params;
+pub mod perft;
+pub mod rng;
+pub mod tt;
+pub mod tune;
+pub mod uci_log;
@@ -1,73 +1,509 @@
-mod evaluation;
-mod engine;
-mod constants;
-mod tt;
-mod opening_book;
-
 use shakmaty::{Chess, Position};
 use std::io::{self, BufRead};
-use crate::engine::Engine;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use betterfish::{bench, perft, tune, uci_log};
+use betterfish::engine::{Engine, PvNotation, QSearchMode, ScorePerspective, SearchLimits, DEFAULT_BOOK_DEPTH, DEFAULT_CONTEMPT, DEFAULT_HASH_MB, DEFAULT_SLOW_MOVER, MAX_DEPTH, MAX_HASH_MB, MAX_SLOW_MOVER, MAX_THREADS};
+use betterfish::opening_book::DEFAULT_BOOK_TEMPERATURE;
+use betterfish::hash::full_hash;
+use betterfish::constants::PIECE_VALUES;
+use betterfish::params::{parse_piece_values, CaptureOrdering, EvalKind, EvalMode, EvalParams, PstSet};
 
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(String::as_str) {
+        Some("tune") => {
+            if let Err(e) = tune::run(&args[2..]) {
+                eprintln!("tune: {}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some("bench") => {
+            // A failed `bench check` exits non-zero so CI can catch an unintended search change
+            let bench_args: Vec<&str> = args[2..].iter().map(String::as_str).collect();
+            match bench::command(&bench_args) {
+                Ok(true) => return,
+                Ok(false) => std::process::exit(1),
+                Err(e) => {
+                    eprintln!("bench: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        _ => {}
+    }
+
     let stdin = io::stdin();
     let mut pos = Chess::default();
-    let depth = 6; // Increased depth
-    let mut engine = Engine::new();
+    // Hashes of the positions before `pos` in the current game
+    let mut history: Vec<u64> = Vec::new();
+    // The search runs on its own thread so `isready` and `stop` are answered while it thinks.
+    // Anything touching engine state locks it, so a running search is stopped first.
+    let engine = Arc::new(Mutex::new(Engine::new()));
+    let stop = engine.lock().unwrap().stop_flag();
+    let debug = engine.lock().unwrap().debug_flag();
+    let pondering = engine.lock().unwrap().pondering_flag();
+    // Set by `quit` before the search is stopped: the GUI is gone, so no bestmove either
+    let quitting = Arc::new(AtomicBool::new(false));
+    let mut search: Option<JoinHandle<()>> = None;
 
     for line in stdin.lock().lines() {
-        let line = line.unwrap();
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                eprintln!("stdin: {}", e);
+                break;
+            }
+        };
+        uci_log::received(&line);
         let parts: Vec<&str> = line.split_whitespace().collect();
         if parts.is_empty() { continue; }
 
         match parts[0] {
             "uci" => {
-                println!("id name BetterFish");
-                println!("id author Gemini CLI");
-                println!("uciok");
-            }
-            "isready" => println!("readyok"),
-            "ucinewgame" => {
-                pos = Chess::default();
-                engine = Engine::new();
+                uci_log::send("id name BetterFish");
+                uci_log::send("id author Gemini CLI");
+                uci_log::send(&format!("option name Hash type spin default {} min 1 max {}", DEFAULT_HASH_MB, MAX_HASH_MB));
+                uci_log::send(&format!("option name Threads type spin default 1 min 1 max {}", MAX_THREADS));
+                uci_log::send(&format!("option name BookTemperature type string default {}", DEFAULT_BOOK_TEMPERATURE));
+                uci_log::send(&format!("option name BookDepth type spin default {} min 0 max {}", DEFAULT_BOOK_DEPTH, DEFAULT_BOOK_DEPTH));
+                uci_log::send("option name EvalFile type string default <empty>");
+                uci_log::send("option name PSTFile type string default <empty>");
+                uci_log::send(&format!("option name PieceValues type string default {}", PIECE_VALUES.map(|v| v.to_string()).join(" ")));
+                uci_log::send(&format!("option name Contempt type spin default {} min -100 max 100", DEFAULT_CONTEMPT));
+                uci_log::send("option name UCI_DrawScore type spin default 0 min -100 max 100");
+                uci_log::send("option name PVNotation type combo default uci var uci var san");
+                uci_log::send("option name ScorePerspective type combo default stm var stm var white");
+                uci_log::send("option name Debug type check default false");
+                uci_log::send("option name NullMovePruning type check default true");
+                uci_log::send("option name Ponder type check default false");
+                uci_log::send("option name PreferShortMates type check default true");
+                uci_log::send("option name UCI_AnalyseMode type check default false");
+                uci_log::send("option name LogFile type string default <empty>");
+                uci_log::send("option name Seed type string default <empty>");
+                uci_log::send("option name EvalNoise type spin default 0 min 0 max 100");
+                uci_log::send("option name EvalMode type combo default full var full var material");
+                uci_log::send("option name Use NNUE type check default false");
+                uci_log::send("option name QSearch type combo default full var full var shallow");
+                uci_log::send("option name CaptureOrdering type combo default see var see var mvvlva");
+                uci_log::send("option name MultiPV type spin default 1 min 1 max 64");
+                uci_log::send(&format!("option name SlowMover type spin default {} min 10 max {}", DEFAULT_SLOW_MOVER, MAX_SLOW_MOVER));
+                uci_log::send(&format!("option name MaxDepth type spin default {} min 1 max {}", MAX_DEPTH, MAX_DEPTH));
+                uci_log::send("uciok");
             }
-            "position" => {
-                if parts.len() > 1 {
-                    if parts[1] == "startpos" {
-                        pos = Chess::default();
-                        if parts.len() > 2 && parts[2] == "moves" {
-                            update_position(&mut pos, &parts[3..]);
+            "isready" => uci_log::send("readyok"),
+            "setoption" => {
+                if let Some((name, value)) = parse_setoption(&parts) {
+                    // Not meant to arrive mid-search, but waiting on the search's lock would hang
+                    // an infinite or ponder search for good: stop it first, as `go` does
+                    finish_search(&mut search, &stop);
+                    let mut engine = engine.lock().unwrap();
+                    match name.to_lowercase().as_str() {
+                        "threads" => {
+                            if let Ok(n) = value.parse::<usize>() {
+                                engine.set_threads(n);
+                            }
+                        }
+                        "hash" => {
+                            if let Ok(mb) = value.parse::<usize>() {
+                                engine.set_hash_size(mb);
+                            }
                         }
-                    } else if parts[1] == "fen" {
-                        let fen_str = parts[2..8].join(" ");
-                        if let Ok(p) = fen_str.parse::<shakmaty::fen::Fen>() {
-                            if let Ok(p_chess) = p.into_position::<Chess>(shakmaty::CastlingMode::Standard) {
-                                pos = p_chess;
+                        "bookdepth" => {
+                            if let Ok(plies) = value.parse::<u32>() {
+                                engine.set_book_depth(plies);
                             }
                         }
-                        if let Some(moves_idx) = parts.iter().position(|&r| r == "moves") {
-                            update_position(&mut pos, &parts[moves_idx + 1..]);
+                        "booktemperature" => {
+                            if let Ok(t) = value.parse::<f64>() {
+                                engine.set_book_temperature(t);
+                            }
                         }
+                        "contempt" => {
+                            if let Ok(c) = value.parse::<i32>() {
+                                engine.set_contempt(c.clamp(-100, 100));
+                            }
+                        }
+                        "uci_drawscore" => {
+                            if let Ok(d) = value.parse::<i32>() {
+                                engine.set_draw_score(d.clamp(-100, 100));
+                            }
+                        }
+                        "pvnotation" => {
+                            match value.to_lowercase().as_str() {
+                                "uci" => engine.set_pv_notation(PvNotation::Uci),
+                                "san" => engine.set_pv_notation(PvNotation::San),
+                                _ => {}
+                            }
+                        }
+                        "scoreperspective" => {
+                            match value.to_lowercase().as_str() {
+                                "stm" => engine.set_score_perspective(ScorePerspective::SideToMove),
+                                "white" => engine.set_score_perspective(ScorePerspective::White),
+                                _ => {}
+                            }
+                        }
+                        "qsearch" => {
+                            match value.to_lowercase().as_str() {
+                                "full" => engine.set_qsearch_mode(QSearchMode::Full),
+                                "shallow" => engine.set_qsearch_mode(QSearchMode::Shallow),
+                                _ => {}
+                            }
+                        }
+                        "captureordering" => {
+                            match value.to_lowercase().as_str() {
+                                "see" => engine.set_capture_ordering(CaptureOrdering::See),
+                                "mvvlva" => engine.set_capture_ordering(CaptureOrdering::MvvLva),
+                                _ => {}
+                            }
+                        }
+                        "multipv" => {
+                            if let Ok(n) = value.parse::<usize>() {
+                                engine.set_multi_pv(n.clamp(1, 64));
+                            }
+                        }
+                        "slowmover" => {
+                            if let Ok(percent) = value.parse::<u64>() {
+                                engine.set_slow_mover(percent);
+                            }
+                        }
+                        "maxdepth" => {
+                            if let Ok(d) = value.parse::<u32>() {
+                                engine.set_max_depth(d);
+                            }
+                        }
+                        "use nnue" => engine.set_eval_kind(if value.eq_ignore_ascii_case("true") { EvalKind::Nnue } else { EvalKind::HandCrafted }),
+                        "evalmode" => {
+                            if let Some(mode) = EvalMode::parse(&value) {
+                                engine.set_eval_mode(mode);
+                            }
+                        }
+                        "evalnoise" => {
+                            if let Ok(noise) = value.parse::<i32>() {
+                                engine.set_eval_noise(noise.clamp(0, 100));
+                            }
+                        }
+                        "logfile" => {
+                            uci_log::close();
+                            if !value.is_empty() && value != "<empty>" && let Err(e) = uci_log::open(&value) {
+                                eprintln!("LogFile: {}", e);
+                            }
+                        }
+                        "seed" => {
+                            // Anything but a number (including <empty>) leaves the current seed alone
+                            if let Ok(seed) = value.parse::<u64>() {
+                                engine.set_seed(seed);
+                            }
+                        }
+                        "debug" => engine.set_debug(value.eq_ignore_ascii_case("true")),
+                        "nullmovepruning" => engine.set_null_move(!value.eq_ignore_ascii_case("false")),
+                        "ponder" => engine.set_ponder(value.eq_ignore_ascii_case("true")),
+                        "prefershortmates" => engine.set_prefer_short_mates(!value.eq_ignore_ascii_case("false")),
+                        "uci_analysemode" => engine.set_analyse_mode(value.eq_ignore_ascii_case("true")),
+                        "evalfile" => {
+                            // An empty or unreadable file keeps the compiled defaults
+                            if value.is_empty() || value == "<empty>" {
+                                engine.set_eval_params(EvalParams::default());
+                            } else {
+                                match EvalParams::load(&value) {
+                                    Ok(params) => engine.set_eval_params(params),
+                                    Err(e) => eprintln!("EvalFile: {}", e),
+                                }
+                            }
+                        }
+                        "piecevalues" => {
+                            // Anything but five values (including <empty>) restores the compiled ones
+                            match parse_piece_values(&value) {
+                                Ok(values) => engine.set_piece_values(values),
+                                Err(e) => {
+                                    if !value.is_empty() && value != "<empty>" {
+                                        eprintln!("PieceValues: {}", e);
+                                    }
+                                    engine.set_piece_values(PIECE_VALUES);
+                                }
+                            }
+                        }
+                        "pstfile" => {
+                            // A malformed file leaves the compiled tables in place
+                            if value.is_empty() || value == "<empty>" {
+                                engine.set_pst(PstSet::default());
+                            } else {
+                                match PstSet::load(&value) {
+                                    Ok(pst) => engine.set_pst(pst),
+                                    Err(e) => {
+                                        eprintln!("PSTFile: {}", e);
+                                        engine.set_pst(PstSet::default());
+                                    }
+                                }
+                            }
+                        }
+                        _ => {}
                     }
                 }
             }
+            "ucinewgame" => {
+                pos = Chess::default();
+                history.clear();
+                finish_search(&mut search, &stop);
+                engine.lock().unwrap().reset();
+            }
+            "position" => {
+                if let Err(e) = set_position(&parts[1..], &mut pos, &mut history) {
+                    eprintln!("position: {}", e);
+                }
+            }
             "go" => {
-                let best_move = engine.find_best_move(&pos, depth);
-                if let Some(m) = best_move {
-                    println!("bestmove {}", m.to_uci(shakmaty::CastlingMode::Standard));
+                finish_search(&mut search, &stop);
+                stop.store(false, Ordering::Relaxed);
+                let limits = parse_go(&pos, &parts[1..]);
+                pondering.store(parts.contains(&"ponder"), Ordering::Relaxed);
+                let (engine, pos, history, quitting) = (Arc::clone(&engine), pos.clone(), history.clone(), Arc::clone(&quitting));
+                search = Some(thread::spawn(move || {
+                    let mut engine = engine.lock().unwrap();
+                    engine.set_game_history(history);
+                    let best_move = engine.find_best_move(&pos, &limits);
+                    let uci = best_move.as_ref().map_or("(none)".to_string(), |m| m.to_uci(shakmaty::CastlingMode::Standard).to_string());
+                    // Every go gets its bestmove, `(none)` when the game is already over
+                    if !quitting.load(Ordering::Relaxed) {
+                        match best_move.as_ref().and_then(|best| engine.ponder_move(best)) {
+                            Some(reply) => uci_log::send(&format!("bestmove {} ponder {}", uci, reply.to_uci(shakmaty::CastlingMode::Standard))),
+                            None => uci_log::send(&format!("bestmove {}", uci)),
+                        }
+                    }
+                    let fen = shakmaty::fen::Fen::from_position(pos, shakmaty::EnPassantMode::Legal);
+                    uci_log::move_summary(&format!("{} bestmove {} {}", fen, uci, engine.search_summary()));
+                }));
+            }
+            "stop" => finish_search(&mut search, &stop),
+            // The expected move was played: the ponder search carries on under the clock
+            "ponderhit" => pondering.store(false, Ordering::Relaxed),
+            "debug" => match parts.get(1) {
+                Some(&"on") => debug.store(true, Ordering::Relaxed),
+                Some(&"off") => debug.store(false, Ordering::Relaxed),
+                _ => {}
+            },
+            // Free engine: nothing to register, and GUIs don't expect a reply
+            "register" => {}
+            "bench" => {
+                if let Err(e) = bench::command(&parts[1..]) {
+                    eprintln!("bench: {}", e);
+                }
+            }
+            "perft" => {
+                if let Some(depth) = parts.get(1).and_then(|d| d.parse().ok()) {
+                    perft::run(&pos, depth);
                 }
             }
             "quit" => break,
             _ => {}
         }
     }
+    // `quit`, or the GUI closed the pipe: nobody is left to read a bestmove
+    quitting.store(true, Ordering::Relaxed);
+    finish_search(&mut search, &stop);
+    uci_log::close();
+}
+
+// Interrupts a running search and waits for its thread to finish reporting
+fn finish_search(search: &mut Option<JoinHandle<()>>, stop: &AtomicBool) {
+    if let Some(handle) = search.take() {
+        stop.store(true, Ordering::Relaxed);
+        let _ = handle.join();
+    }
 }
 
-fn update_position(pos: &mut Chess, moves: &[&str]) {
+// "position startpos|fen <fen> [moves <m1> <m2> ...]". The FEN runs up to `moves`, so the
+// halfmove and fullmove counters may be left out. Some GUIs send "position moves ..." meaning
+// the start position. A bad FEN, or no position at all, leaves `pos` untouched.
+fn set_position(parts: &[&str], pos: &mut Chess, history: &mut Vec<u64>) -> Result<(), String> {
+    let moves_idx = parts.iter().position(|&p| p == "moves").unwrap_or(parts.len());
+    let start = match parts.first() {
+        Some(&"startpos") | Some(&"moves") => Chess::default(),
+        Some(&"fen") => {
+            let fen = parts[1..moves_idx].join(" ");
+            fen.parse::<shakmaty::fen::Fen>().map_err(|e| format!("bad FEN '{}': {}", fen, e))?
+                .into_position(shakmaty::CastlingMode::Standard).map_err(|e| format!("illegal FEN '{}': {}", fen, e))?
+        }
+        _ => return Err("expected startpos or fen".to_string()),
+    };
+    *pos = start;
+    history.clear();
+    update_position(pos, parts.get(moves_idx + 1..).unwrap_or(&[]), history)
+}
+
+// Stops at the first move that doesn't parse or isn't legal, leaving `pos` after the last good one
+// `history` receives the hash of every position left behind, for repetition detection
+fn update_position(pos: &mut Chess, moves: &[&str], history: &mut Vec<u64>) -> Result<(), String> {
     for m_str in moves {
-        if let Ok(m) = m_str.parse::<shakmaty::uci::UciMove>() {
-            if let Ok(m_actual) = m.to_move(pos) {
-                pos.play_unchecked(&m_actual);
+        let uci = m_str.parse::<shakmaty::uci::UciMove>().map_err(|e| format!("cannot parse move '{}': {}", m_str, e))?;
+        let m = uci.to_move(pos).map_err(|e| {
+            let fen = shakmaty::fen::Fen::from_position(pos.clone(), shakmaty::EnPassantMode::Legal);
+            format!("move '{}' rejected in {}: {}", m_str, fen, e)
+        })?;
+        history.push(full_hash(pos));
+        pos.play_unchecked(&m);
+    }
+    Ok(())
+}
+
+// "go [depth <n>] [nodes <n>] [movetime <ms>] [wtime <ms>] [btime <ms>] [winc <ms>] [binc <ms>]
+// [movestogo <n>] [infinite] [searchmoves <m1> <m2> ...]" in any order - searchmoves runs until
+// the next keyword. Unknown tokens (mate...) and values that don't parse are skipped; `ponder`
+// is picked up by the caller, since the limits it carries only apply from ponderhit.
+fn parse_go(pos: &Chess, parts: &[&str]) -> SearchLimits {
+    let mut limits = SearchLimits::default();
+    let mut depth_given = false;
+    // Only consumed when it parses, so a missing value can't swallow the next keyword
+    let value = |i: &mut usize| -> Option<u64> {
+        let v = parts.get(*i + 1)?.parse::<u64>().ok()?;
+        *i += 1;
+        Some(v)
+    };
+    let mut i = 0;
+    while i < parts.len() {
+        match parts[i] {
+            "depth" => {
+                if let Some(d) = value(&mut i) {
+                    limits.depth = (d.min(MAX_DEPTH as u64) as u32).max(1);
+                    depth_given = true;
+                }
             }
+            "nodes" => limits.nodes = value(&mut i),
+            "movetime" => limits.movetime = value(&mut i),
+            "wtime" => limits.wtime = value(&mut i),
+            "btime" => limits.btime = value(&mut i),
+            "winc" => limits.winc = value(&mut i).unwrap_or(0),
+            "binc" => limits.binc = value(&mut i).unwrap_or(0),
+            "movestogo" => limits.movestogo = value(&mut i).map(|n| n as u32),
+            "infinite" => {
+                limits.depth = MAX_DEPTH;
+                limits.infinite = true;
+            }
+            "searchmoves" => {
+                while let Some(m) = parts.get(i + 1).and_then(|m| m.parse::<shakmaty::uci::UciMove>().ok()) {
+                    if let Ok(m) = m.to_move(pos) {
+                        limits.search_moves.push(m);
+                    }
+                    i += 1;
+                }
+            }
+            _ => {}
         }
+        i += 1;
+    }
+    // A bare node or time limit shouldn't be cut short by the default depth
+    let timed = limits.movetime.is_some() || limits.wtime.is_some() || limits.btime.is_some();
+    if (limits.nodes.is_some() || timed) && !depth_given {
+        limits.depth = MAX_DEPTH;
     }
-}
\ No newline at end of file
+    limits
+}
+
+// "setoption name <id> [value <x>]" - both id and value may contain spaces
+fn parse_setoption(parts: &[&str]) -> Option<(String, String)> {
+    let name_idx = parts.iter().position(|&p| p == "name")?;
+    let value_idx = parts.iter().position(|&p| p == "value");
+    let name_end = value_idx.unwrap_or(parts.len());
+    if name_end <= name_idx + 1 { return None; }
+
+    let name = parts[name_idx + 1..name_end].join(" ");
+    let value = value_idx.map(|i| parts[i + 1..].join(" ")).unwrap_or_default();
+    Some((name, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn update_position_stops_at_illegal_move() {
+        let mut pos = Chess::default();
+        let mut history = Vec::new();
+        let result = update_position(&mut pos, &["e2e4", "e7e5", "e1e3", "g1f3"], &mut history);
+        assert!(result.unwrap_err().contains("e1e3"));
+
+        let mut expected = Chess::default();
+        let mut expected_history = Vec::new();
+        update_position(&mut expected, &["e2e4", "e7e5"], &mut expected_history).unwrap();
+        assert_eq!(pos, expected);
+        assert_eq!(history, expected_history);
+        assert_eq!(history[0], full_hash(&Chess::default()));
+
+        assert!(update_position(&mut Chess::default(), &["e2e4", "xyz"], &mut Vec::new()).is_err());
+    }
+
+    fn position_fen(command: &str) -> String {
+        let parts: Vec<&str> = command.split_whitespace().collect();
+        let mut pos = Chess::default();
+        set_position(&parts, &mut pos, &mut Vec::new()).unwrap();
+        shakmaty::fen::Fen::from_position(pos, shakmaty::EnPassantMode::Legal).to_string()
+    }
+
+    #[test]
+    fn set_position_applies_special_moves_from_a_fen() {
+        assert_eq!(position_fen("fen 8/4P1k1/8/8/8/8/8/4K3 w - - 0 1 moves e7e8q"), "4Q3/6k1/8/8/8/8/8/4K3 b - - 0 1");
+        assert_eq!(position_fen("fen 8/8/8/8/8/8/4P1k1/4K3 w - - 0 1 moves e2e4 g2f3 e1d2 f3e4"), "8/8/8/8/4k3/8/3K4/8 w - - 0 3");
+        assert_eq!(position_fen("fen 4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2 moves e5d6"), "4k3/8/3P4/8/8/8/8/4K3 b - - 0 2");
+        assert_eq!(position_fen("fen 4k3/8/8/8/4p3/8/3P4/4K3 w - - 0 1 moves d2d4 e4d3"), "4k3/8/8/8/8/3p4/8/4K3 w - - 0 2");
+        assert_eq!(position_fen("fen r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1 moves e1g1 e8c8"), "2kr3r/8/8/8/8/8/8/R4RK1 w - - 2 2");
+        assert_eq!(position_fen("fen r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1 moves e1c1 e8g8"), "r4rk1/8/8/8/8/8/8/2KR3R w - - 2 2");
+        // Counters left off, as some GUIs do
+        assert_eq!(position_fen("fen 4k3/8/8/8/8/8/8/4K2R w K - moves e1g1"), "4k3/8/8/8/8/8/8/5RK1 b - - 1 1");
+    }
+
+    #[test]
+    fn set_position_rejects_a_bad_fen() {
+        let mut pos = Chess::default();
+        let mut history = Vec::new();
+        assert!(set_position(&["fen", "not", "a", "fen"], &mut pos, &mut history).is_err());
+        assert!(set_position(&[], &mut pos, &mut history).is_err());
+        assert_eq!(pos, Chess::default());
+    }
+
+    #[test]
+    fn set_position_without_a_start_means_startpos() {
+        assert_eq!(position_fen("moves e2e4 e7e5"), position_fen("startpos moves e2e4 e7e5"));
+
+        // A bare `position` changes nothing, history included
+        let mut pos = Chess::default();
+        let mut history = Vec::new();
+        set_position(&["startpos", "moves", "d2d4"], &mut pos, &mut history).unwrap();
+        let (before, before_history) = (pos.clone(), history.clone());
+        assert!(set_position(&[], &mut pos, &mut history).is_err());
+        assert_eq!(pos, before);
+        assert_eq!(history, before_history);
+    }
+
+    #[test]
+    fn parse_go_reads_depth_and_searchmoves() {
+        let pos = Chess::default();
+        let limits = parse_go(&pos, &["searchmoves", "e2e4", "d2d4", "depth", "4"]);
+        assert_eq!(limits.depth, 4);
+        let moves: Vec<String> = limits.search_moves.iter().map(|m| m.to_uci(shakmaty::CastlingMode::Standard).to_string()).collect();
+        assert_eq!(moves, ["e2e4", "d2d4"]);
+    }
+
+    #[test]
+    fn parse_go_accepts_gui_strings_in_any_order() {
+        let pos = Chess::default();
+        let go = |s: &str| parse_go(&pos, &s.split_whitespace().collect::<Vec<_>>());
+        let clock = |wtime, btime, inc| SearchLimits { wtime: Some(wtime), btime: Some(btime), winc: inc, binc: inc, ..SearchLimits::depth(MAX_DEPTH) };
+
+        // Arena
+        assert_eq!(go("btime 300000 wtime 300000 winc 0 binc 0"), clock(300_000, 300_000, 0));
+        // cutechess-cli, with a repeating time control
+        assert_eq!(go("wtime 120000 btime 118500 movestogo 40"), SearchLimits { movestogo: Some(40), ..clock(120_000, 118_500, 0) });
+        // lichess-bot, pondering
+        assert_eq!(go("wtime 180000 btime 179000 winc 2000 binc 2000 ponder"), clock(180_000, 179_000, 2_000));
+        // Clock and depth together, depth last
+        assert_eq!(go("wtime 1000 btime 1000 movestogo 40 depth 20"), SearchLimits { depth: 20, movestogo: Some(40), ..clock(1_000, 1_000, 0) });
+        assert_eq!(go("movetime 5000"), SearchLimits { movetime: Some(5_000), ..SearchLimits::depth(MAX_DEPTH) });
+        assert_eq!(go("depth 20 movetime 5000"), SearchLimits { movetime: Some(5_000), ..SearchLimits::depth(20) });
+
+        // Junk is skipped, and a keyword missing its value doesn't eat the next one
+        assert_eq!(go("foo 3 depth 5 mate 2"), SearchLimits::depth(5));
+        assert_eq!(go("wtime depth 7"), SearchLimits::depth(7));
+    }
+}
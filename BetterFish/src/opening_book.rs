@@ -1,130 +1,255 @@
 use shakmaty::{Chess, EnPassantMode};
 use shakmaty::fen::Epd;
+use shakmaty::uci::UciMove;
 use std::collections::HashMap;
+use crate::rng::Rng;
+
+pub const DEFAULT_BOOK_TEMPERATURE: f64 = 1.0;
 
 pub struct OpeningBook {
-    book: HashMap<String, Vec<String>>,
+    book: HashMap<String, Vec<(String, u32)>>,
+    rng: Rng,
+    // 0 = always the most played move, 1 = proportional to weight, large = uniform
+    temperature: f64,
+}
+
+impl Default for OpeningBook {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl OpeningBook {
     pub fn new() -> Self {
         let mut book = HashMap::new();
-        let mut add = |fen: &str, m_str: &str| {
-            book.entry(fen.to_string()).or_insert_with(Vec::new).push(m_str.to_string());
+        let mut add = |fen: &str, m_str: &str, weight: Option<u32>| {
+            book.entry(fen.to_string()).or_insert_with(Vec::new).push((m_str.to_string(), weight.unwrap_or(1)));
         };
 
         // --- STARTING POSITION ---
         let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -";
-        for m in &["e2e4", "d2d4", "c2c4", "g1f3", "b1c3", "f2f4", "b2b3", "g2g3"] { add(start, m); }
+        for (m, w) in &[("e2e4", 40), ("d2d4", 35), ("c2c4", 10), ("g1f3", 10), ("b1c3", 1), ("f2f4", 1), ("b2b3", 1), ("g2g3", 2)] { add(start, m, Some(*w)); }
 
         // --- 1. e4 OPENINGS ---
         let e4 = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq -";
-        for m in &["c7c5", "e7e5", "e7e6", "c7c6", "d7d5", "g8f6", "d7d6", "g7g6", "b7b6", "a7a6", "h7h6"] { add(e4, m); }
+        for (m, w) in &[("c7c5", 35), ("e7e5", 30), ("e7e6", 12), ("c7c6", 10), ("d7d5", 4), ("g8f6", 2), ("d7d6", 3), ("g7g6", 2), ("b7b6", 1), ("a7a6", 1), ("h7h6", 1)] { add(e4, m, Some(*w)); }
 
         // --- 1. d4 OPENINGS ---
         let d4 = "rnbqkbnr/pppppppp/8/8/3P4/8/PPP1PPPP/RNBQKBNR b KQkq -";
-        for m in &["g8f6", "d7d5", "f7f5", "e7e6", "c7c6", "g7g6", "d7d6"] { add(d4, m); }
+        for (m, w) in &[("g8f6", 45), ("d7d5", 35), ("f7f5", 4), ("e7e6", 8), ("c7c6", 3), ("g7g6", 3), ("d7d6", 2)] { add(d4, m, Some(*w)); }
 
         // --- 1. c4 (English) ---
         let c4 = "rnbqkbnr/pppppppp/8/8/2P5/8/PP1PPPPP/RNBQKBNR b KQkq -";
-        for m in &["e7e5", "c7c5", "g8f6", "e7e6"] { add(c4, m); }
+        for m in &["e7e5", "c7c5", "g8f6", "e7e6"] { add(c4, m, None); }
 
         // --- 1. f3 (Reti) ---
         let nf3 = "rnbqkbnr/pppppppp/8/8/8/5N2/PPPPPPPP/RNBQKB1R b KQkq -";
-        for m in &["d7d5", "g8f6", "c7c5"] { add(nf3, m); }
+        for m in &["d7d5", "g8f6", "c7c5"] { add(nf3, m, None); }
 
         // --- SICILIAN DEFENSE (1. e4 c5) ---
         let sicilian = "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq -";
-        for m in &["g1f3", "b1c3", "d2d3", "c2c3"] { add(sicilian, m); }
+        for m in &["g1f3", "b1c3", "d2d3", "c2c3"] { add(sicilian, m, None); }
         
         // Open Sicilian (2. Nf3)
         let open_sicilian = "rnbqkbnr/pp1ppppp/8/2p5/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq -";
-        for m in &["d7d6", "e7e6", "b8c6", "g7g6", "a7a6"] { add(open_sicilian, m); }
+        for m in &["d7d6", "e7e6", "b8c6", "g7g6", "a7a6"] { add(open_sicilian, m, None); }
 
         // Najdorf (1. e4 c5 2. Nf3 d6 3. d4 cxd4 4. Nxd4 Nf6 5. Nc3 a6)
         let najdorf_pos = "r1bqkb1r/1p2pppp/p2p1n2/8/3NP3/2N5/PPP2PPP/R1BQKB1R w KQkq -";
-        for m in &["c1g5", "f2f4", "f1e2", "h2h3", "g2g4", "a2a4"] { add(najdorf_pos, m); }
+        for m in &["c1g5", "f2f4", "f1e2", "h2h3", "g2g4", "a2a4"] { add(najdorf_pos, m, None); }
 
         // --- RUY LOPEZ (1. e4 e5 2. Nf3 Nc6 3. Bb5) ---
         let ruy_lopez = "r1bqkbnr/pppp1ppp/2n5/1B2p3/4P3/5N2/PPPP1PPP/RNBQK2R b KQkq -";
-        for m in &["a7a6", "g8f6", "d7d6", "f7f5", "g7g6", "b8c6"] { add(ruy_lopez, m); }
+        for m in &["a7a6", "g8f6", "d7d6", "f7f5", "g7g6", "b8c6"] { add(ruy_lopez, m, None); }
 
         // Morphay Defense (3... a6)
         let morphay = "r1bqkbnr/1ppp1ppp/p1n5/1B2p3/4P3/5N2/PPPP1PPP/RNBQK2R w KQkq -";
-        for m in &["b5a4", "b5xc6"] { add(morphay, m); }
+        for m in &["b5a4", "b5xc6"] { add(morphay, m, None); }
 
         // Exchange Variation (4. Bxc6)
         let ruy_exchange = "r1bqkbnr/1ppp1ppp/2N5/4p3/4P3/5N2/PPPP1PPP/RNBQK2R b KQkq -";
-        for m in &["d7xc6", "b7xc6"] { add(ruy_exchange, m); }
+        for m in &["d7xc6", "b7xc6"] { add(ruy_exchange, m, None); }
 
         // --- ITALIAN GAME (1. e4 e5 2. Nf3 Nc6 3. Bc4) ---
         let italian = "r1bqkbnr/pppp1ppp/2n5/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R b KQkq -";
-        for m in &["f8c5", "g8f6", "h7h6"] { add(italian, m); }
+        for m in &["f8c5", "g8f6", "h7h6"] { add(italian, m, None); }
 
         // --- FRENCH DEFENSE (1. e4 e6 2. d4 d5) ---
         let french = "rnbqkbnr/pppp1ppp/4p3/8/3PP3/8/PPP2PPP/RNBQKBNR b KQkq -";
-        for m in &["d7d5", "c7c5"] { add(french, m); }
+        for m in &["d7d5", "c7c5"] { add(french, m, None); }
         
         let french_main = "rnbqkbnr/ppp2ppp/4p3/3p4/3PP3/8/PPP2PPP/RNBQKBNR w KQkq -";
-        for m in &["b1c3", "b1d2", "e4e5", "e4xd5"] { add(french_main, m); }
+        for m in &["b1c3", "b1d2", "e4e5", "e4xd5"] { add(french_main, m, None); }
 
         // --- QUEEN'S GAMBIT (1. d4 d5 2. c4) ---
         let qg = "rnbqkbnr/ppp1pppp/8/3p4/2PP4/8/PP2PPPP/RNBQKBNR b KQkq -";
-        for m in &["e7e6", "c7c6", "d5xc4", "e7e5", "g8f6"] { add(qg, m); }
+        for m in &["e7e6", "c7c6", "d5xc4", "e7e5", "g8f6"] { add(qg, m, None); }
 
         // --- KING'S INDIAN (1. d4 Nf6 2. c4 g6) ---
         let kid = "rnbqkb1r/pppppp1p/5np1/8/2PP4/8/PP2PPPP/RNBQKBNR w KQkq -";
-        for m in &["b1c3", "g1f3", "g2g3"] { add(kid, m); }
+        for m in &["b1c3", "g1f3", "g2g3"] { add(kid, m, None); }
 
         // --- CARO-KANN (1. e4 c6 2. d4 d5) ---
         let caro_kann = "rnbqkbnr/pp1ppppp/2p5/8/3PP3/8/PPP2PPP/RNBQKBNR b KQkq -";
-        for m in &["d7d5"] { add(caro_kann, m); }
+        add(caro_kann, "d7d5", None);
         
         let caro_main = "rnbqkbnr/pp2pppp/2p5/3p4/3PP3/8/PPP2PPP/RNBQKBNR w KQkq -";
-        for m in &["b1c3", "e4e5", "e4xd5", "b1d2"] { add(caro_main, m); }
+        for m in &["b1c3", "e4e5", "e4xd5", "b1d2"] { add(caro_main, m, None); }
 
         // --- SCANDINAVIAN (1. e4 d5 2. exd5 Qxd5) ---
         let scandi_open = "rnbqkbnr/ppp1pppp/8/3P4/8/8/PPPP1PPP/RNBQKBNR b KQkq -";
-        for m in &["d8xd5", "g8f6"] { add(scandi_open, m); }
+        for m in &["d8xd5", "g8f6"] { add(scandi_open, m, None); }
 
         // --- LONDON SYSTEM (1. d4 d5 2. Bf4) ---
         let london = "rnbqkbnr/ppp1pppp/8/3p4/3P1B2/8/PPP1PPPP/RN1QKBNR b KQkq -";
-        for m in &["g8f6", "c7c5", "e7e6"] { add(london, m); }
+        for m in &["g8f6", "c7c5", "e7e6"] { add(london, m, None); }
 
         // --- NIMZO-INDIAN (1. d4 Nf6 2. c4 e6 3. Nc3 Bb4) ---
         let nimzo = "rnbqkb1r/pppp1ppp/4pn2/8/2PP4/2N5/PP2PPPP/R1BQKBNR b KQkq -";
-        for m in &["f8b4", "d7d5", "c7c5"] { add(nimzo, m); }
+        for m in &["f8b4", "d7d5", "c7c5"] { add(nimzo, m, None); }
 
         // --- GRUENFELD (1. d4 Nf6 2. c4 g6 3. Nc3 d5) ---
         let gruenfeld = "rnbqkb1r/ppp1pp1p/5np1/3p4/2PP4/2N5/PP2PPPP/R1BQKBNR w KQkq -";
-        for m in &["c4xd5", "g1f3", "c1f4", "c1g5"] { add(gruenfeld, m); }
+        for m in &["c4xd5", "g1f3", "c1f4", "c1g5"] { add(gruenfeld, m, None); }
 
         // --- BENONI (1. d4 Nf6 2. c4 c5 3. d5) ---
         let benoni = "rnbqkb1r/pppppppp/5n2/2pP4/8/8/PP2PPPP/RNBQKBNR b KQkq -";
-        for m in &["e7e6", "d7d6", "g7g6"] { add(benoni, m); }
+        for m in &["e7e6", "d7d6", "g7g6"] { add(benoni, m, None); }
 
         // --- DUTCH DEFENSE (1. d4 f5) ---
         let dutch = "rnbqkbnr/ppppp1pp/8/5p2/3P4/8/PPP1PPPP/RNBQKBNR w KQkq -";
-        for m in &["c2c4", "g1f3", "g2g3", "c1g5"] { add(dutch, m); }
+        for m in &["c2c4", "g1f3", "g2g3", "c1g5"] { add(dutch, m, None); }
 
         // --- CATALAN (1. d4 Nf6 2. c4 e6 3. g3 d5) ---
         let catalan = "rnbqkb1r/ppp2ppp/4pn2/3p4/2PP4/6P1/PP2PP1P/RNBQKBNR w KQkq -";
-        for m in &["f1g2", "g1f3"] { add(catalan, m); }
+        for m in &["f1g2", "g1f3"] { add(catalan, m, None); }
+
+        Self { book, rng: Rng::from_time(), temperature: DEFAULT_BOOK_TEMPERATURE }
+    }
+
+    pub fn set_rng(&mut self, rng: Rng) {
+        self.rng = rng;
+    }
 
-        Self { book }
+    pub fn set_temperature(&mut self, temperature: f64) {
+        self.temperature = temperature.max(0.0);
     }
 
-    pub fn get_move(&self, pos: &Chess) -> Option<String> {
-        let epd = Epd::from_position(pos.clone(), EnPassantMode::Always);
-        let epd_string = format!("{}", epd);
-        let parts: Vec<&str> = epd_string.split_whitespace().collect();
-        if parts.len() >= 4 {
-            let key = parts[0..4].join(" ");
-            if let Some(moves) = self.book.get(&key) {
-                let idx = (std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_nanos() % moves.len() as u128) as usize;
-                return Some(moves[idx].clone());
+    fn pick(&mut self, moves: &[(String, u32)]) -> usize {
+        let mut best = 0;
+        for (i, (_, w)) in moves.iter().enumerate() {
+            if *w > moves[best].1 { best = i; }
+        }
+        let top = moves[best].1;
+        if self.temperature <= 0.0 || top == 0 { return best; }
+
+        // Normalising by the top weight keeps w^(1/T) in [0, 1] for any temperature
+        let scaled: Vec<f64> = moves.iter().map(|(_, w)| (*w as f64 / top as f64).powf(1.0 / self.temperature)).collect();
+        let total: f64 = scaled.iter().sum();
+
+        let mut r = self.rng.next_f64() * total;
+        for (i, s) in scaled.iter().enumerate() {
+            if r < *s { return i; }
+            r -= s;
+        }
+        moves.len() - 1
+    }
+
+    // Book keys only carry an en passant square when the capture is actually legal, so a double
+    // pawn push nobody can take reaches the same entry by any move order.
+    pub fn get_move(&mut self, pos: &Chess) -> Option<String> {
+        let epd = Epd::from_position(pos.clone(), EnPassantMode::Legal).to_string();
+        let parts: Vec<&str> = epd.split_whitespace().collect();
+        if parts.len() < 4 { return None; }
+
+        let mut moves = self.book.get(&parts[0..4].join(" ")).map(|m| legal_entries(pos, m)).unwrap_or_default();
+
+        // Same pieces and side to move but different castling or en passant rights: borrow that
+        // entry's moves, keeping only those still legal here
+        if moves.is_empty() {
+            let placement = format!("{} {} ", parts[0], parts[1]);
+            let mut keys: Vec<&String> = self.book.keys().filter(|k| k.starts_with(&placement)).collect();
+            keys.sort();
+            for key in keys {
+                for entry in legal_entries(pos, &self.book[key]) {
+                    match moves.iter_mut().find(|(m, _)| *m == entry.0) {
+                        Some(existing) => existing.1 += entry.1,
+                        None => moves.push(entry),
+                    }
+                }
             }
         }
-        None
+
+        if moves.is_empty() { return None; }
+        let idx = self.pick(&moves);
+        Some(moves[idx].0.clone())
+    }
+}
+
+fn legal_entries(pos: &Chess, moves: &[(String, u32)]) -> Vec<(String, u32)> {
+    moves.iter()
+        .filter(|(m, _)| m.parse::<UciMove>().is_ok_and(|uci| uci.to_move(pos).is_ok()))
+        .cloned()
+        .collect()
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shakmaty::Position;
+
+    #[test]
+    fn zero_temperature_always_plays_top_weighted_move() {
+        let mut book = OpeningBook::new();
+        book.set_temperature(0.0);
+        for _ in 0..20 {
+            assert_eq!(book.get_move(&Chess::default()).as_deref(), Some("e2e4"));
+        }
+    }
+
+    #[test]
+    fn unit_temperature_picks_moves_in_proportion_to_weight() {
+        let mut book = OpeningBook::new();
+        book.set_rng(Rng::new(42));
+        book.set_temperature(1.0);
+        let draws = 5000;
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for _ in 0..draws {
+            *counts.entry(book.get_move(&Chess::default()).unwrap()).or_default() += 1;
+        }
+        // Start position weights sum to 100, so each is a percentage
+        for (m, weight) in [("e2e4", 40.0), ("d2d4", 35.0), ("c2c4", 10.0), ("g1f3", 10.0), ("g2g3", 2.0)] {
+            let share = counts.get(m).copied().unwrap_or(0) as f64 * 100.0 / draws as f64;
+            assert!((share - weight).abs() < 3.0, "{} played {:.1}% for weight {}", m, share, weight);
+        }
     }
-}
\ No newline at end of file
+
+    fn play(moves: &[&str]) -> Chess {
+        let mut pos = Chess::default();
+        for m in moves {
+            let m = m.parse::<UciMove>().unwrap().to_move(&pos).unwrap();
+            pos.play_unchecked(&m);
+        }
+        pos
+    }
+
+    #[test]
+    fn transposed_move_orders_reach_the_same_entry() {
+        let mut book = OpeningBook::new();
+        book.set_temperature(0.0);
+        // 1. c4 d5 2. d4 is the Queen's Gambit; d2d4 leaves an uncapturable en passant square
+        let qg = book.get_move(&play(&["d2d4", "d7d5", "c2c4"]));
+        assert_eq!(qg.as_deref(), Some("e7e6"));
+        assert_eq!(book.get_move(&play(&["c2c4", "d7d5", "d2d4"])), qg);
+    }
+
+    #[test]
+    fn lost_castling_rights_fall_back_to_placement() {
+        let mut book = OpeningBook::new();
+        book.set_temperature(0.0);
+        // French main line after White's king has wandered e1-e2-e1: no castling rights, same pieces
+        let pos = play(&["e2e4", "e7e6", "e1e2", "d7d5", "e2e1", "g8f6", "d2d4", "f6g8"]);
+        let m = book.get_move(&pos).expect("placement match");
+        assert!(["b1c3", "b1d2", "e4e5", "e4d5"].contains(&m.as_str()), "{}", m);
+        assert!(m.parse::<UciMove>().unwrap().to_move(&pos).is_ok());
+    }
+}
@@ -0,0 +1,461 @@
+use shakmaty::Role;
+use crate::constants::*;
+use std::fs;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EvalMode {
+    Full,
+    // Material + PST only, for bullet time controls where node rate matters more
+    Material,
+}
+
+impl EvalMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "full" => Some(EvalMode::Full),
+            "material" => Some(EvalMode::Material),
+            _ => None,
+        }
+    }
+}
+
+// Which evaluator `evaluate` hands the position to, chosen with the Use NNUE option. There is no
+// network yet: Nnue falls back to the hand-crafted eval until EvalFile can load weights.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EvalKind {
+    HandCrafted,
+    Nnue,
+}
+
+// How captures are ranked among themselves in move ordering. MVV-LVA is a table lookup; SEE plays
+// out the exchange, so it costs more but puts captures of defended pieces behind safe ones.
+// Chosen with the CaptureOrdering option.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CaptureOrdering {
+    MvvLva,
+    See,
+}
+
+// Piece-square tables as used by the eval, one middlegame and one endgame table per role,
+// laid out like the compiled constants (a8 first). Roles without a separate endgame table
+// start with the same values in both.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PstSet {
+    mg: [[i32; 64]; 6],
+    eg: [[i32; 64]; 6],
+}
+
+impl Default for PstSet {
+    fn default() -> Self {
+        Self {
+            mg: [MG_PAWN_PST, MG_KNIGHT_PST, MG_BISHOP_PST, ROOK_PST, QUEEN_PST, MG_KING_PST],
+            eg: [EG_PAWN_PST, MG_KNIGHT_PST, MG_BISHOP_PST, ROOK_PST, QUEEN_PST, EG_KING_PST],
+        }
+    }
+}
+
+// Indexed by `Role as usize - 1`, as the tables are
+const PST_ROLES: [&str; 6] = ["pawn", "knight", "bishop", "rook", "queen", "king"];
+
+impl PstSet {
+    pub fn get(&self, role: Role, index: usize) -> (i32, i32) {
+        let r = role as usize - 1;
+        (self.mg[r][index], self.eg[r][index])
+    }
+
+    // `pawn_mg = ...` through `king_eg = ...`, 64 values each. A table may continue over the
+    // following lines until the next `key =`, so it can be laid out 8 by 8. Tables not in the
+    // file keep their defaults.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut tables: Vec<(String, String, usize)> = Vec::new();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() { continue; }
+            match line.split_once('=') {
+                Some((name, values)) => tables.push((name.trim().to_lowercase(), values.to_string(), n + 1)),
+                None => match tables.last_mut() {
+                    Some((_, values, _)) => { values.push(' '); values.push_str(line); }
+                    None => return Err(format!("line {}: expected key=value", n + 1)),
+                },
+            }
+        }
+
+        let mut pst = Self::default();
+        for (name, values, n) in tables {
+            let (role, phase) = name.split_once('_').ok_or_else(|| format!("line {}: unknown table '{}'", n, name))?;
+            let r = PST_ROLES.iter().position(|&name| name == role)
+                .ok_or_else(|| format!("line {}: unknown table '{}'", n, name))?;
+            let table = match phase {
+                "mg" => &mut pst.mg[r],
+                "eg" => &mut pst.eg[r],
+                _ => return Err(format!("line {}: unknown table '{}'", n, name)),
+            };
+            *table = parse_array(&values).map_err(|e| format!("line {}: {}", n, e))?;
+        }
+        Ok(pst)
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        Self::parse(&text)
+    }
+}
+
+// Runtime copy of the evaluation weights so they can be tuned without recompiling.
+// Defaults are the compiled constants.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EvalParams {
+    pub passed_pawn_bonus: [i32; 8],
+    pub blockader_bonus: [i32; 6],
+    pub isolated_pawn_penalty: i32,
+    pub chain_base_penalty: i32,
+    pub pawn_tension_bonus: i32,
+    pub doubled_pawn_penalty: [i32; 8],
+    pub isolated_doubled_penalty: i32,
+    pub doubled_pawn_rook_compensation: i32,
+    pub rook_on_open_file: i32,
+    pub rook_on_semi_open_file: i32,
+    pub rook_attacks_weak_pawn: i32,
+    pub king_shield_bonus: i32,
+    pub fianchetto_bonus: i32,
+    pub fianchetto_hole_penalty: i32,
+    pub king_semi_open_file_penalty: i32,
+    pub king_open_file_penalty: i32,
+    pub king_flight_penalty: i32,
+    pub rook_on_7th_bonus: i32,
+    pub heavy_infiltration_bonus: i32,
+    pub confined_king_factor: i32,
+    pub rook_behind_passer: i32,
+    pub knight_outpost_bonus: i32,
+    pub bishop_outpost_bonus: i32,
+    pub knight_rim_penalty: i32,
+    pub bad_bishop_penalty: i32,
+    pub pawn_majority_bonus: i32,
+    pub minority_attack_bonus: i32,
+    pub mop_up_edge: i32,
+    pub mop_up_kings: i32,
+    pub stalemate_risk_penalty: i32,
+    pub bishop_open_bonus: i32,
+    pub knight_closed_bonus: i32,
+    pub connectivity_bonus: i32,
+    pub developed_minor_bonus: i32,
+    pub castled_bonus: i32,
+    pub early_queen_penalty: i32,
+    pub bishop_pair_bonus: i32,
+    pub locked_bishop_pair_bonus: i32,
+    pub pawn_storm_bonus: [i32; 8],
+    pub mobility_mg: [i32; 6],
+    pub mobility_eg: [i32; 6],
+    pub shield_hole_penalty: i32,
+    pub tropism_weight: [i32; 6],
+    // Set with the PieceValues option and, like the PSTs, kept when an EvalFile is loaded
+    pub piece_values: [i32; 5],
+    // Loaded with PSTFile rather than EvalFile, and kept when an EvalFile is loaded
+    pub pst: PstSet,
+    // Not a weight: chosen by the EvalMode option and kept when an EvalFile is loaded
+    pub mode: EvalMode,
+    // Likewise chosen by the Use NNUE option
+    pub kind: EvalKind,
+}
+
+impl Default for EvalParams {
+    fn default() -> Self {
+        Self {
+            passed_pawn_bonus: PASSED_PAWN_BONUS,
+            blockader_bonus: BLOCKADER_BONUS,
+            isolated_pawn_penalty: ISOLATED_PAWN_PENALTY,
+            chain_base_penalty: CHAIN_BASE_PENALTY,
+            pawn_tension_bonus: PAWN_TENSION_BONUS,
+            doubled_pawn_penalty: DOUBLED_PAWN_PENALTY,
+            isolated_doubled_penalty: ISOLATED_DOUBLED_PENALTY,
+            doubled_pawn_rook_compensation: DOUBLED_PAWN_ROOK_COMPENSATION,
+            rook_on_open_file: ROOK_ON_OPEN_FILE,
+            rook_on_semi_open_file: ROOK_ON_SEMI_OPEN_FILE,
+            rook_attacks_weak_pawn: ROOK_ATTACKS_WEAK_PAWN,
+            king_shield_bonus: KING_SHIELD_BONUS,
+            fianchetto_bonus: FIANCHETTO_BONUS,
+            fianchetto_hole_penalty: FIANCHETTO_HOLE_PENALTY,
+            king_semi_open_file_penalty: KING_SEMI_OPEN_FILE_PENALTY,
+            king_open_file_penalty: KING_OPEN_FILE_PENALTY,
+            king_flight_penalty: KING_FLIGHT_PENALTY,
+            rook_on_7th_bonus: ROOK_ON_7TH_BONUS,
+            heavy_infiltration_bonus: HEAVY_INFILTRATION_BONUS,
+            confined_king_factor: CONFINED_KING_FACTOR,
+            rook_behind_passer: ROOK_BEHIND_PASSER,
+            knight_outpost_bonus: KNIGHT_OUTPOST_BONUS,
+            bishop_outpost_bonus: BISHOP_OUTPOST_BONUS,
+            knight_rim_penalty: KNIGHT_RIM_PENALTY,
+            bad_bishop_penalty: BAD_BISHOP_PENALTY,
+            pawn_majority_bonus: PAWN_MAJORITY_BONUS,
+            minority_attack_bonus: MINORITY_ATTACK_BONUS,
+            mop_up_edge: MOP_UP_EDGE,
+            mop_up_kings: MOP_UP_KINGS,
+            stalemate_risk_penalty: STALEMATE_RISK_PENALTY,
+            bishop_open_bonus: BISHOP_OPEN_BONUS,
+            knight_closed_bonus: KNIGHT_CLOSED_BONUS,
+            connectivity_bonus: CONNECTIVITY_BONUS,
+            developed_minor_bonus: DEVELOPED_MINOR_BONUS,
+            castled_bonus: CASTLED_BONUS,
+            early_queen_penalty: EARLY_QUEEN_PENALTY,
+            bishop_pair_bonus: BISHOP_PAIR_BONUS,
+            locked_bishop_pair_bonus: LOCKED_BISHOP_PAIR_BONUS,
+            pawn_storm_bonus: PAWN_STORM_BONUS,
+            mobility_mg: MOBILITY_MG,
+            mobility_eg: MOBILITY_EG,
+            shield_hole_penalty: SHIELD_HOLE_PENALTY,
+            tropism_weight: TROPISM_WEIGHT,
+            piece_values: PIECE_VALUES,
+            pst: PstSet::default(),
+            mode: EvalMode::Full,
+            kind: EvalKind::HandCrafted,
+        }
+    }
+}
+
+// Search pruning and reduction settings, gathered in one place for tuning (SPSA and the like).
+// Defaults are the values the search has always used, apart from SEE capture ordering.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchParams {
+    // Null move: tried from this remaining depth, and searched this many plies shallower
+    pub null_move_min_depth: u32,
+    pub null_move_reduction: u32,
+    // At depth 1, a static eval this far above beta cuts off without searching
+    pub futility_margin: i32,
+    // Late move reductions start at this move index (later in PV nodes) and remaining depth, then
+    // grow by one ply per `lmr_move_step` moves, capped at depth / `lmr_depth_divisor`
+    pub lmr_min_move: usize,
+    pub lmr_min_move_pv: usize,
+    pub lmr_min_depth: u32,
+    pub lmr_move_step: u32,
+    pub lmr_depth_divisor: u32,
+    // History score above which a late move is reduced one ply less
+    pub lmr_history_high: i32,
+    // Internal iterative deepening: without a hash move from this depth, a search this much shallower finds one
+    pub iid_min_depth: u32,
+    pub iid_reduction: u32,
+    // Half-width of the root search window once depth is high
+    pub aspiration_delta: i32,
+    pub capture_ordering: CaptureOrdering,
+    // Smallest capture plus promotion gain shallow quiescence looks at: a minor piece, so pawn
+    // grabs are left out
+    pub qsearch_shallow_min_gain: i32,
+}
+
+impl Default for SearchParams {
+    fn default() -> Self {
+        Self {
+            null_move_min_depth: 3,
+            null_move_reduction: 3,
+            futility_margin: 160,
+            lmr_min_move: 4,
+            lmr_min_move_pv: 6,
+            lmr_min_depth: 3,
+            lmr_move_step: 4,
+            lmr_depth_divisor: 3,
+            lmr_history_high: 2000,
+            iid_min_depth: 4,
+            iid_reduction: 2,
+            aspiration_delta: ASPIRATION_DELTA,
+            capture_ordering: CaptureOrdering::See,
+            qsearch_shallow_min_gain: 300,
+        }
+    }
+}
+
+impl SearchParams {
+    // Whether a quiet move this late in the list may be searched reduced. PV nodes hold off
+    // longer, since their score is the one that gets reported.
+    pub fn lmr_applies(&self, move_index: usize, depth: u32, pv_node: bool) -> bool {
+        let min_move = if pv_node { self.lmr_min_move_pv } else { self.lmr_min_move };
+        move_index >= min_move && depth >= self.lmr_min_depth
+    }
+
+    // Late moves are reduced more the later they come, then nudged by history: one ply less for
+    // moves that have often caused cutoffs, one more for moves that never have or mostly failed to.
+    pub fn lmr_reduction(&self, move_index: usize, depth: u32, history: i32) -> u32 {
+        let base = 1 + (move_index as u32 / self.lmr_move_step).min(depth / self.lmr_depth_divisor);
+        if history >= self.lmr_history_high {
+            (base - 1).max(1)
+        } else if history <= 0 {
+            base + 1
+        } else {
+            base
+        }
+    }
+
+    // The most plies one reduction can take off a subtree at this depth: a late move with no
+    // history behind it, or the null move
+    pub fn max_reduction(&self, depth: u32) -> u32 {
+        let lmr = if depth >= self.lmr_min_depth { self.lmr_reduction(usize::MAX, depth, 0) } else { 0 };
+        let null = if depth >= self.null_move_min_depth { self.null_move_reduction } else { 0 };
+        lmr.max(null)
+    }
+}
+
+fn parse_int(value: &str) -> Result<i32, String> {
+    value.trim().parse::<i32>().map_err(|_| format!("invalid value '{}'", value.trim()))
+}
+
+fn parse_array<const N: usize>(value: &str) -> Result<[i32; N], String> {
+    let values = value.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|v| !v.is_empty())
+        .map(parse_int)
+        .collect::<Result<Vec<i32>, String>>()?;
+    values.try_into().map_err(|v: Vec<i32>| format!("expected {} values, got {}", N, v.len()))
+}
+
+// Five values, pawn to queen, for the PieceValues option. GUIs may pass them quoted.
+pub fn parse_piece_values(value: &str) -> Result<[i32; 5], String> {
+    parse_array(value.trim().trim_matches('"'))
+}
+
+impl EvalParams {
+    // The king is never traded, so its value stays fixed
+    pub fn material_value(&self, role: Role) -> i32 {
+        match role {
+            Role::King => KING_VALUE,
+            _ => self.piece_values[role as usize - 1],
+        }
+    }
+
+    // Term names are the lowercase constant names, e.g. `rook_on_open_file = 30`.
+    // Array terms take comma or space separated values.
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        match name.trim().to_lowercase().as_str() {
+            "passed_pawn_bonus" => self.passed_pawn_bonus = parse_array(value)?,
+            "blockader_bonus" => self.blockader_bonus = parse_array(value)?,
+            "isolated_pawn_penalty" => self.isolated_pawn_penalty = parse_int(value)?,
+            "chain_base_penalty" => self.chain_base_penalty = parse_int(value)?,
+            "pawn_tension_bonus" => self.pawn_tension_bonus = parse_int(value)?,
+            "doubled_pawn_penalty" => self.doubled_pawn_penalty = parse_array(value)?,
+            "isolated_doubled_penalty" => self.isolated_doubled_penalty = parse_int(value)?,
+            "doubled_pawn_rook_compensation" => self.doubled_pawn_rook_compensation = parse_int(value)?,
+            "rook_on_open_file" => self.rook_on_open_file = parse_int(value)?,
+            "rook_on_semi_open_file" => self.rook_on_semi_open_file = parse_int(value)?,
+            "rook_attacks_weak_pawn" => self.rook_attacks_weak_pawn = parse_int(value)?,
+            "king_shield_bonus" => self.king_shield_bonus = parse_int(value)?,
+            "fianchetto_bonus" => self.fianchetto_bonus = parse_int(value)?,
+            "fianchetto_hole_penalty" => self.fianchetto_hole_penalty = parse_int(value)?,
+            "king_semi_open_file_penalty" => self.king_semi_open_file_penalty = parse_int(value)?,
+            "king_open_file_penalty" => self.king_open_file_penalty = parse_int(value)?,
+            "king_flight_penalty" => self.king_flight_penalty = parse_int(value)?,
+            "rook_on_7th_bonus" => self.rook_on_7th_bonus = parse_int(value)?,
+            "heavy_infiltration_bonus" => self.heavy_infiltration_bonus = parse_int(value)?,
+            "confined_king_factor" => self.confined_king_factor = parse_int(value)?,
+            "rook_behind_passer" => self.rook_behind_passer = parse_int(value)?,
+            "knight_outpost_bonus" => self.knight_outpost_bonus = parse_int(value)?,
+            "bishop_outpost_bonus" => self.bishop_outpost_bonus = parse_int(value)?,
+            "knight_rim_penalty" => self.knight_rim_penalty = parse_int(value)?,
+            "bad_bishop_penalty" => self.bad_bishop_penalty = parse_int(value)?,
+            "pawn_majority_bonus" => self.pawn_majority_bonus = parse_int(value)?,
+            "minority_attack_bonus" => self.minority_attack_bonus = parse_int(value)?,
+            "mop_up_edge" => self.mop_up_edge = parse_int(value)?,
+            "mop_up_kings" => self.mop_up_kings = parse_int(value)?,
+            "stalemate_risk_penalty" => self.stalemate_risk_penalty = parse_int(value)?,
+            "bishop_open_bonus" => self.bishop_open_bonus = parse_int(value)?,
+            "knight_closed_bonus" => self.knight_closed_bonus = parse_int(value)?,
+            "connectivity_bonus" => self.connectivity_bonus = parse_int(value)?,
+            "developed_minor_bonus" => self.developed_minor_bonus = parse_int(value)?,
+            "castled_bonus" => self.castled_bonus = parse_int(value)?,
+            "early_queen_penalty" => self.early_queen_penalty = parse_int(value)?,
+            "bishop_pair_bonus" => self.bishop_pair_bonus = parse_int(value)?,
+            "locked_bishop_pair_bonus" => self.locked_bishop_pair_bonus = parse_int(value)?,
+            "pawn_storm_bonus" => self.pawn_storm_bonus = parse_array(value)?,
+            "mobility_mg" => self.mobility_mg = parse_array(value)?,
+            "mobility_eg" => self.mobility_eg = parse_array(value)?,
+            "shield_hole_penalty" => self.shield_hole_penalty = parse_int(value)?,
+            "tropism_weight" => self.tropism_weight = parse_array(value)?,
+            other => return Err(format!("unknown eval term '{}'", other)),
+        }
+        Ok(())
+    }
+
+    // `key = value` per line, `#` starts a comment. Terms not in the file keep their defaults.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut params = Self::default();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() { continue; }
+            let (name, value) = line.split_once('=').ok_or_else(|| format!("line {}: expected key=value", n + 1))?;
+            params.set(name, value).map_err(|e| format!("line {}: {}", n + 1, e))?;
+        }
+        Ok(params)
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        Self::parse(&text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_overrides_only_listed_terms() {
+        let params = EvalParams::parse("# tuned\nrook_on_open_file = 40\npassed_pawn_bonus = 0, 5, 10, 20, 40, 80, 160, 0\n").unwrap();
+        assert_eq!(params.rook_on_open_file, 40);
+        assert_eq!(params.passed_pawn_bonus, [0, 5, 10, 20, 40, 80, 160, 0]);
+        assert_eq!(params.knight_outpost_bonus, KNIGHT_OUTPOST_BONUS);
+
+        assert!(EvalParams::parse("no_such_term = 1").is_err());
+        assert!(EvalParams::parse("pawn_storm_bonus = 1 2 3").is_err());
+    }
+
+    #[test]
+    fn search_params_default_to_the_historical_values() {
+        let params = SearchParams::default();
+        assert_eq!((params.null_move_min_depth, params.null_move_reduction), (3, 3));
+        assert_eq!(params.futility_margin, 160);
+        assert_eq!((params.lmr_min_move, params.lmr_min_depth, params.lmr_move_step, params.lmr_depth_divisor), (4, 3, 4, 3));
+        assert_eq!(params.lmr_history_high, 2000);
+        assert_eq!((params.iid_min_depth, params.iid_reduction), (4, 2));
+        assert_eq!(params.aspiration_delta, 50);
+        assert_eq!(params.capture_ordering, CaptureOrdering::See);
+        assert_eq!(params.qsearch_shallow_min_gain, 300);
+    }
+
+    #[test]
+    fn pv_nodes_reduce_fewer_moves() {
+        let params = SearchParams::default();
+        let reduced = |depth, pv_node| (0..40).filter(|&i| params.lmr_applies(i, depth, pv_node)).count();
+        assert!(reduced(6, true) < reduced(6, false), "pv {} vs non-pv {}", reduced(6, true), reduced(6, false));
+        assert!(!params.lmr_applies(params.lmr_min_move, 6, true));
+        assert!(params.lmr_applies(params.lmr_min_move, 6, false));
+        assert_eq!(reduced(params.lmr_min_depth - 1, false), 0);
+    }
+
+    #[test]
+    fn max_reduction_covers_every_late_move_and_the_null_move() {
+        let params = SearchParams::default();
+        assert_eq!(params.max_reduction(params.lmr_min_depth - 1), 0);
+        for depth in params.lmr_min_depth..20 {
+            let deepest = (0..200).map(|i| params.lmr_reduction(i, depth, -1)).max().unwrap();
+            assert_eq!(params.max_reduction(depth), deepest.max(params.null_move_reduction));
+        }
+    }
+
+    #[test]
+    fn pst_file_replaces_listed_tables_only() {
+        let rows = "0 0 0 0 0 0 0 0\n".repeat(8);
+        let pst = PstSet::parse(&format!("# flat pawns\npawn_mg =\n{}", rows)).unwrap();
+        assert_eq!(pst.get(Role::Pawn, 8), (0, EG_PAWN_PST[8]));
+        assert_eq!(pst.get(Role::Knight, 0), (MG_KNIGHT_PST[0], MG_KNIGHT_PST[0]));
+
+        assert!(PstSet::parse("pawn_mg = 1 2 3").is_err());
+        assert!(PstSet::parse("pawn_xx = 0").is_err());
+        assert!(PstSet::parse("0 0 0").is_err());
+    }
+
+    #[test]
+    fn piece_values_need_exactly_five_values() {
+        assert_eq!(parse_piece_values("\"100 350 330 500 900\""), Ok([100, 350, 330, 500, 900]));
+        assert!(parse_piece_values("100 320 330 500").is_err());
+        assert!(parse_piece_values("100 320 330 500 900 20000").is_err());
+
+        let params = EvalParams { piece_values: [100, 300, 300, 500, 1000], ..EvalParams::default() };
+        assert_eq!(params.material_value(Role::Queen), 1000);
+        assert_eq!(params.material_value(Role::King), KING_VALUE);
+    }
+}
@@ -0,0 +1,62 @@
+use shakmaty::{CastlingMode, Chess, Move, Position};
+use std::time::Instant;
+
+// Leaf count of the legal move tree; the standard check on move generation and play.
+pub fn perft(pos: &Chess, depth: u32) -> u64 {
+    if depth == 0 { return 1; }
+    let moves = pos.legal_moves();
+    if depth == 1 { return moves.len() as u64; }
+
+    moves.iter().map(|m| {
+        let mut next = pos.clone();
+        next.play_unchecked(m);
+        perft(&next, depth - 1)
+    }).sum()
+}
+
+// Per-root-move counts, to narrow a perft mismatch down to the move that causes it.
+pub fn perft_divide(pos: &Chess, depth: u32) -> Vec<(Move, u64)> {
+    if depth == 0 { return Vec::new(); }
+    pos.legal_moves().into_iter().map(|m| {
+        let mut next = pos.clone();
+        next.play_unchecked(&m);
+        let nodes = perft(&next, depth - 1);
+        (m, nodes)
+    }).collect()
+}
+
+// `perft <depth>`: one `move: nodes` line per root move, then the total
+pub fn run(pos: &Chess, depth: u32) -> u64 {
+    let start = Instant::now();
+    let divide = perft_divide(pos, depth);
+    for (m, nodes) in &divide {
+        println!("{}: {}", m.to_uci(CastlingMode::Standard), nodes);
+    }
+    let total = divide.iter().map(|(_, nodes)| nodes).sum();
+    println!();
+    println!("Nodes searched: {} ({} ms)", total, start.elapsed().as_millis());
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn start_position_divide_matches_reference() {
+        let pos = Chess::default();
+        let divide = perft_divide(&pos, 3);
+        let count = |uci: &str| divide.iter()
+            .find(|(m, _)| m.to_uci(CastlingMode::Standard).to_string() == uci)
+            .map(|(_, nodes)| *nodes);
+
+        assert_eq!(divide.len(), 20);
+        assert_eq!(count("e2e4"), Some(600));
+        assert_eq!(count("d2d4"), Some(560));
+        assert_eq!(count("b2b4"), Some(421));
+        assert_eq!(count("g1f3"), Some(440));
+        assert_eq!(count("a2a3"), Some(380));
+        assert_eq!(divide.iter().map(|(_, nodes)| nodes).sum::<u64>(), 8902);
+        assert_eq!(perft(&pos, 4), 197281);
+    }
+}
@@ -1,16 +1,255 @@
-use shakmaty::Move;
+use shakmaty::{Move, Role, Square};
+use std::sync::atomic::{AtomicU64, Ordering};
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum NodeType {
     Exact,
     LowerBound,
     UpperBound,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct TTEntry {
     pub depth: u32,
     pub score: i32,
     pub node_type: NodeType,
     pub best_move: Option<Move>,
+    // The score depends on the path to this node: a repetition draw was found below it, so
+    // reached another way the same position could be worth something else (graph history
+    // interaction). Such entries are used for move ordering only, never for cutoffs.
+    pub path_dependent: bool,
+}
+
+// An entry packed into one word:
+//   bits  0-23  best move (bit 23 set when there is one, see pack_move)
+//   bits 24-39  score as i16
+//   bits 40-47  depth, saturating at 255
+//   bits 48-49  node type
+//   bit  50     path_dependent
+//   bit  51     set in every stored entry, so an empty slot never matches
+const SCORE_SHIFT: u32 = 24;
+const DEPTH_SHIFT: u32 = 40;
+const NODE_TYPE_SHIFT: u32 = 48;
+const PATH_DEPENDENT_BIT: u64 = 1 << 50;
+const USED_BIT: u64 = 1 << 51;
+const MOVE_PRESENT_BIT: u64 = 1 << 23;
+
+impl TTEntry {
+    pub fn pack(&self) -> u64 {
+        let score = self.score.clamp(i16::MIN as i32, i16::MAX as i32) as i16 as u16 as u64;
+        let node_type = match self.node_type {
+            NodeType::Exact => 0,
+            NodeType::LowerBound => 1,
+            NodeType::UpperBound => 2,
+        };
+        self.best_move.as_ref().map_or(0, pack_move)
+            | score << SCORE_SHIFT
+            | (self.depth.min(255) as u64) << DEPTH_SHIFT
+            | node_type << NODE_TYPE_SHIFT
+            | if self.path_dependent { PATH_DEPENDENT_BIT } else { 0 }
+            | USED_BIT
+    }
+
+    pub fn unpack(data: u64) -> Self {
+        let node_type = match (data >> NODE_TYPE_SHIFT) & 3 {
+            0 => NodeType::Exact,
+            1 => NodeType::LowerBound,
+            _ => NodeType::UpperBound,
+        };
+        Self {
+            depth: ((data >> DEPTH_SHIFT) & 0xff) as u32,
+            score: ((data >> SCORE_SHIFT) & 0xffff) as u16 as i16 as i32,
+            node_type,
+            best_move: unpack_move(data),
+            path_dependent: data & PATH_DEPENDENT_BIT != 0,
+        }
+    }
+}
+
+// from (or king) in bits 0-5, to (or rook) in 6-11, then three bits each for the moving role,
+// the captured role and the promotion (0 for none), and two for the kind of move
+fn pack_move(m: &Move) -> u64 {
+    let role_bits = |role: Option<Role>| role.map_or(0, |r| r as u64);
+    let (from, to, role, capture, promotion, kind) = match *m {
+        Move::Normal { role, from, capture, to, promotion } => (from, to, Some(role), capture, promotion, 0),
+        Move::EnPassant { from, to } => (from, to, Some(Role::Pawn), Some(Role::Pawn), None, 1),
+        Move::Castle { king, rook } => (king, rook, Some(Role::King), None, None, 2),
+        Move::Put { role, to } => (to, to, Some(role), None, None, 3),
+    };
+    from as u64 | (to as u64) << 6 | role_bits(role) << 12 | role_bits(capture) << 15
+        | role_bits(promotion) << 18 | kind << 21 | MOVE_PRESENT_BIT
+}
+
+fn unpack_move(data: u64) -> Option<Move> {
+    if data & MOVE_PRESENT_BIT == 0 { return None; }
+    let from = Square::new((data & 63) as u32);
+    let to = Square::new(((data >> 6) & 63) as u32);
+    let role = |shift: u32| Role::try_from((data >> shift) & 7).ok();
+    Some(match (data >> 21) & 3 {
+        0 => Move::Normal { role: role(12)?, from, capture: role(15), to, promotion: role(18) },
+        1 => Move::EnPassant { from, to },
+        2 => Move::Castle { king: from, rook: to },
+        _ => Move::Put { role: role(12)?, to },
+    })
+}
+
+// Two words per slot: the packed entry and the position key XORed with it. A slot written by one
+// thread while another reads it can hand back a key from one entry and data from another; the
+// XOR then no longer gives the probed key, so a torn read is a miss rather than a wrong entry.
+struct Slot {
+    key: AtomicU64,
+    data: AtomicU64,
+}
+
+// Counters since the last reset_stats, for judging whether the Hash size fits the search.
+// A collision is a store that evicts the entry of a different position.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TTStats {
+    pub probes: u64,
+    pub hits: u64,
+    pub stores: u64,
+    pub collisions: u64,
+    // Permille of slots in use, as hashfull
+    pub occupancy: usize,
+}
+
+// Fixed-size, always-replace table indexed by the key modulo the slot count. Shared references are
+// enough to probe and store, so search threads can use one table without a lock.
+pub struct TranspositionTable {
+    slots: Vec<Slot>,
+    probes: AtomicU64,
+    hits: AtomicU64,
+    stores: AtomicU64,
+    collisions: AtomicU64,
+}
+
+impl TranspositionTable {
+    pub fn new(mb: usize) -> Self {
+        let count = (mb * 1024 * 1024 / std::mem::size_of::<Slot>()).max(1);
+        Self {
+            slots: (0..count).map(|_| Slot { key: AtomicU64::new(0), data: AtomicU64::new(0) }).collect(),
+            probes: AtomicU64::new(0),
+            hits: AtomicU64::new(0),
+            stores: AtomicU64::new(0),
+            collisions: AtomicU64::new(0),
+        }
+    }
+
+    fn slot(&self, hash: u64) -> &Slot {
+        &self.slots[(hash % self.slots.len() as u64) as usize]
+    }
+
+    pub fn probe(&self, hash: u64) -> Option<TTEntry> {
+        let slot = self.slot(hash);
+        let key = slot.key.load(Ordering::Relaxed);
+        let data = slot.data.load(Ordering::Relaxed);
+        self.probes.fetch_add(1, Ordering::Relaxed);
+        let hit = data & USED_BIT != 0 && key ^ data == hash;
+        if hit { self.hits.fetch_add(1, Ordering::Relaxed); }
+        hit.then(|| TTEntry::unpack(data))
+    }
+
+    pub fn store(&self, hash: u64, entry: &TTEntry) {
+        let slot = self.slot(hash);
+        let old = slot.data.load(Ordering::Relaxed);
+        if old & USED_BIT != 0 && slot.key.load(Ordering::Relaxed) ^ old != hash {
+            self.collisions.fetch_add(1, Ordering::Relaxed);
+        }
+        self.stores.fetch_add(1, Ordering::Relaxed);
+        let data = entry.pack();
+        slot.key.store(hash ^ data, Ordering::Relaxed);
+        slot.data.store(data, Ordering::Relaxed);
+    }
+
+    pub fn clear(&mut self) {
+        for slot in &mut self.slots {
+            *slot.key.get_mut() = 0;
+            *slot.data.get_mut() = 0;
+        }
+    }
+
+    pub fn stats(&self) -> TTStats {
+        TTStats {
+            probes: self.probes.load(Ordering::Relaxed),
+            hits: self.hits.load(Ordering::Relaxed),
+            stores: self.stores.load(Ordering::Relaxed),
+            collisions: self.collisions.load(Ordering::Relaxed),
+            occupancy: self.hashfull(),
+        }
+    }
+
+    pub fn reset_stats(&self) {
+        for counter in [&self.probes, &self.hits, &self.stores, &self.collisions] {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    // Slots holding an entry, per thousand, from a sample at the start of the table
+    pub fn hashfull(&self) -> usize {
+        let sample = &self.slots[..self.slots.len().min(1000)];
+        sample.iter().filter(|s| s.data.load(Ordering::Relaxed) & USED_BIT != 0).count() * 1000 / sample.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+
+    fn random_entry(rng: &mut Rng) -> TTEntry {
+        let square = |rng: &mut Rng| Square::new((rng.next_u64() % 64) as u32);
+        let role = |rng: &mut Rng| Role::try_from(rng.next_u64() % 6 + 1).unwrap();
+        let maybe_role = |rng: &mut Rng| Role::try_from(rng.next_u64() % 7).ok();
+        let best_move = match rng.next_u64() % 5 {
+            0 => None,
+            1 => Some(Move::EnPassant { from: square(rng), to: square(rng) }),
+            2 => Some(Move::Castle { king: square(rng), rook: square(rng) }),
+            3 => Some(Move::Put { role: role(rng), to: square(rng) }),
+            _ => Some(Move::Normal { role: role(rng), from: square(rng), capture: maybe_role(rng), to: square(rng), promotion: maybe_role(rng) }),
+        };
+        let node_type = [NodeType::Exact, NodeType::LowerBound, NodeType::UpperBound][(rng.next_u64() % 3) as usize];
+        TTEntry {
+            depth: (rng.next_u64() % 256) as u32,
+            score: (rng.next_u64() % 80001) as i32 - 40000,
+            node_type,
+            best_move,
+            path_dependent: rng.next_u64() & 1 == 0,
+        }
+    }
+
+    #[test]
+    fn entries_round_trip_through_packing() {
+        let mut rng = Rng::new(7);
+        for _ in 0..10_000 {
+            let entry = random_entry(&mut rng);
+            let expected = TTEntry { score: entry.score.clamp(i16::MIN as i32, i16::MAX as i32), ..entry.clone() };
+            assert_eq!(TTEntry::unpack(entry.pack()), expected);
+        }
+        // Mate scores are well inside the packed range
+        let mate = TTEntry { depth: 300, score: -29990, node_type: NodeType::UpperBound, best_move: None, path_dependent: false };
+        assert_eq!(TTEntry::unpack(mate.pack()), TTEntry { depth: 255, ..mate });
+    }
+
+    #[test]
+    fn probe_rejects_other_keys_and_torn_slots() {
+        let tt = TranspositionTable::new(1);
+        let mut rng = Rng::new(11);
+        let entry = random_entry(&mut rng);
+        let hash = 0x1234_5678_9abc_def0;
+        assert_eq!(tt.probe(hash), None);
+        tt.store(hash, &entry);
+        assert_eq!(tt.probe(hash).map(|e| e.node_type), Some(entry.node_type));
+
+        // Same slot, different key
+        assert_eq!(tt.probe(hash + tt.capacity() as u64), None);
+
+        // Key word from this entry, data word from another: what a racing writer leaves behind
+        let other = TTEntry { path_dependent: !entry.path_dependent, ..entry.clone() };
+        tt.slot(hash).data.store(other.pack(), Ordering::Relaxed);
+        assert_eq!(tt.probe(hash), None);
+    }
 }
@@ -0,0 +1,93 @@
+use shakmaty::{CastlingMode, Chess, Color, Position};
+use shakmaty::fen::Fen;
+use std::fs;
+use crate::evaluation::evaluate;
+use crate::params::EvalParams;
+
+pub const DEFAULT_K: f64 = 1.0;
+
+fn parse_result(token: &str) -> Option<f64> {
+    match token.trim_matches(|c| c == '[' || c == ']' || c == '"') {
+        "1.0" | "1" | "1-0" => Some(1.0),
+        "0.5" | "1/2-1/2" => Some(0.5),
+        "0.0" | "0" | "0-1" => Some(0.0),
+        _ => None,
+    }
+}
+
+// One `FEN result` per line; result is White's score (1.0 / 0.5 / 0.0 or 1-0 / 1/2-1/2 / 0-1).
+pub fn load_dataset(path: &str) -> Result<Vec<(Chess, f64)>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let mut dataset = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') { continue; }
+        let (fen, result) = line.rsplit_once(char::is_whitespace).ok_or_else(|| format!("line {}: expected 'FEN result'", n + 1))?;
+        let result = parse_result(result).ok_or_else(|| format!("line {}: bad result '{}'", n + 1, result))?;
+        let pos = fen.trim().parse::<Fen>().ok()
+            .and_then(|f| f.into_position::<Chess>(CastlingMode::Standard).ok())
+            .ok_or_else(|| format!("line {}: bad FEN", n + 1))?;
+        dataset.push((pos, result));
+    }
+    Ok(dataset)
+}
+
+fn sigmoid(score: f64, k: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-k * score / 400.0))
+}
+
+// Mean squared error between the game results and the win probability predicted by the static eval.
+pub fn texel_error(params: &EvalParams, dataset: &[(Chess, f64)], k: f64) -> f64 {
+    if dataset.is_empty() { return 0.0; }
+    let total: f64 = dataset.iter().map(|(pos, result)| {
+        let score = evaluate(pos, params);
+        let white_score = if pos.turn() == Color::White { score } else { -score };
+        let err = result - sigmoid(white_score as f64, k);
+        err * err
+    }).sum();
+    total / dataset.len() as f64
+}
+
+// `tune <dataset> [k] [evalfile]`
+pub fn run(args: &[String]) -> Result<(), String> {
+    let path = args.first().ok_or("usage: tune <dataset> [k] [evalfile]")?;
+    let k = match args.get(1) {
+        Some(k) => k.parse::<f64>().map_err(|_| format!("bad k '{}'", k))?,
+        None => DEFAULT_K,
+    };
+    let params = match args.get(2) {
+        Some(file) => EvalParams::load(file)?,
+        None => EvalParams::default(),
+    };
+
+    let dataset = load_dataset(path)?;
+    println!("positions {} k {} error {:.6}", dataset.len(), k, texel_error(&params, &dataset, k));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(fen: &str, result: f64) -> (Chess, f64) {
+        (fen.parse::<Fen>().unwrap().into_position(CastlingMode::Standard).unwrap(), result)
+    }
+
+    #[test]
+    fn error_is_lower_when_eval_agrees_with_result() {
+        let params = EvalParams::default();
+        let queen_up = "4k3/pppp4/8/8/8/8/PPPP4/3QK3 b - - 0 1";
+        let agrees = texel_error(&params, &[sample(queen_up, 1.0)], DEFAULT_K);
+        let disagrees = texel_error(&params, &[sample(queen_up, 0.0)], DEFAULT_K);
+        assert!(agrees < 0.05);
+        assert!(disagrees > 0.9);
+    }
+
+    #[test]
+    fn parses_result_notations() {
+        assert_eq!(parse_result("1-0"), Some(1.0));
+        assert_eq!(parse_result("[0.5]"), Some(0.5));
+        assert_eq!(parse_result("0-1"), Some(0.0));
+        assert_eq!(parse_result("draw"), None);
+    }
+}
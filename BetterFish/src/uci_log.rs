@@ -0,0 +1,67 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::sync::Mutex;
+
+// LogFile: a transcript of the UCI session for post-game analysis. Global rather than owned by
+// the loop because the search thread sends its own info and bestmove lines.
+static LOG: Mutex<Option<BufWriter<File>>> = Mutex::new(None);
+
+fn write(prefix: &str, line: &str) {
+    if let Some(log) = LOG.lock().unwrap().as_mut() {
+        let _ = writeln!(log, "{}{}", prefix, line);
+    }
+}
+
+// Appends, so one file can collect a whole match
+pub fn open(path: &str) -> Result<(), String> {
+    let file = OpenOptions::new().create(true).append(true).open(path).map_err(|e| format!("{}: {}", path, e))?;
+    *LOG.lock().unwrap() = Some(BufWriter::new(file));
+    Ok(())
+}
+
+pub fn close() {
+    if let Some(mut log) = LOG.lock().unwrap().take() {
+        let _ = log.flush();
+    }
+}
+
+pub fn received(line: &str) {
+    write("<< ", line);
+}
+
+// Everything the engine says goes through here so the log sees exactly what the GUI saw
+pub fn send(line: &str) {
+    println!("{}", line);
+    write(">> ", line);
+}
+
+// Log-only search summary, written once per move, after which the log is flushed
+pub fn move_summary(summary: &str) {
+    let mut guard = LOG.lock().unwrap();
+    if let Some(log) = guard.as_mut() {
+        let _ = writeln!(log, "## {}", summary);
+        let _ = log.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log_records_both_directions_and_summary() {
+        let path = std::env::temp_dir().join(format!("betterfish-log-{}.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+
+        open(path).unwrap();
+        received("isready");
+        send("readyok");
+        move_summary("bestmove e2e4 depth 6");
+        close();
+
+        let text = std::fs::read_to_string(path).unwrap();
+        assert_eq!(text, "<< isready\n>> readyok\n## bestmove e2e4 depth 6\n");
+        std::fs::remove_file(path).unwrap();
+    }
+}
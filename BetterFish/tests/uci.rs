@@ -0,0 +1,253 @@
+use std::io::{BufRead, BufReader, Lines, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use shakmaty::{Chess, Position};
+use shakmaty::uci::UciMove;
+
+fn spawn_engine() -> (Child, ChildStdin, Lines<BufReader<ChildStdout>>) {
+    let mut engine = Command::new(env!("CARGO_BIN_EXE_BetterFish"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("engine starts");
+    let stdin = engine.stdin.take().unwrap();
+    let lines = BufReader::new(engine.stdout.take().unwrap()).lines();
+    (engine, stdin, lines)
+}
+
+#[test]
+fn isready_is_answered_while_searching() {
+    let (mut engine, mut stdin, mut lines) = spawn_engine();
+
+    // Out of book and deep enough that the search is still running when isready arrives
+    writeln!(stdin, "position fen r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N2N2/PP2BPPP/R2QKB1R w KQ - 0 8").unwrap();
+    writeln!(stdin, "go depth 64").unwrap();
+    writeln!(stdin, "isready").unwrap();
+
+    let first = lines.by_ref().map(Result::unwrap).find(|l| l == "readyok" || l.starts_with("bestmove"));
+    assert_eq!(first.as_deref(), Some("readyok"));
+
+    writeln!(stdin, "stop").unwrap();
+    assert!(lines.by_ref().map(Result::unwrap).any(|l| l.starts_with("bestmove")));
+
+    writeln!(stdin, "quit").unwrap();
+    assert!(engine.wait().unwrap().success());
+}
+
+#[test]
+fn go_infinite_runs_until_stop() {
+    let (mut engine, mut stdin, mut lines) = spawn_engine();
+
+    // MaxDepth is reached almost at once, but bestmove must still wait for stop
+    writeln!(stdin, "setoption name MaxDepth value 5").unwrap();
+    writeln!(stdin, "position startpos moves e2e4").unwrap();
+    writeln!(stdin, "go infinite").unwrap();
+    let depth_of = |l: &str| l.strip_prefix("info depth ").and_then(|rest| rest.split_whitespace().next()?.parse::<u32>().ok());
+    assert!(lines.by_ref().map(Result::unwrap).any(|l| depth_of(&l) == Some(5)));
+    std::thread::sleep(std::time::Duration::from_millis(200));
+    writeln!(stdin, "isready").unwrap();
+
+    let mut deepest = 5;
+    let first = lines.by_ref().map(Result::unwrap)
+        .inspect(|l| deepest = deepest.max(depth_of(l).unwrap_or(0)))
+        .find(|l| l == "readyok" || l.starts_with("bestmove"));
+    assert_eq!(first.as_deref(), Some("readyok"));
+
+    writeln!(stdin, "stop").unwrap();
+    let bestmove = lines.by_ref().map(Result::unwrap)
+        .inspect(|l| deepest = deepest.max(depth_of(l).unwrap_or(0)))
+        .find(|l| l.starts_with("bestmove")).unwrap();
+    assert_eq!(deepest, 5, "searched past MaxDepth");
+    let uci = bestmove.split_whitespace().nth(1).unwrap().parse::<UciMove>().unwrap();
+    let mut pos = Chess::default();
+    pos.play_unchecked(&"e2e4".parse::<UciMove>().unwrap().to_move(&pos).unwrap());
+    assert!(uci.to_move(&pos).is_ok(), "{} is not legal", bestmove);
+
+    writeln!(stdin, "quit").unwrap();
+    assert!(engine.wait().unwrap().success());
+}
+
+#[test]
+fn debug_command_toggles_info_strings() {
+    let (mut engine, mut stdin, mut lines) = spawn_engine();
+    let fen = "position fen r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N2N2/PP2BPPP/R2QKB1R w KQ - 0 8";
+    let mut info_strings = |stdin: &mut ChildStdin| {
+        writeln!(stdin, "{}", fen).unwrap();
+        writeln!(stdin, "go depth 3").unwrap();
+        lines.by_ref().map(Result::unwrap)
+            .take_while(|l| !l.starts_with("bestmove"))
+            .filter(|l| l.starts_with("info string"))
+            .count()
+    };
+
+    writeln!(stdin, "debug on").unwrap();
+    assert!(info_strings(&mut stdin) > 0);
+    writeln!(stdin, "debug off").unwrap();
+    assert_eq!(info_strings(&mut stdin), 0);
+
+    // Accepted without a reply
+    writeln!(stdin, "register later").unwrap();
+    writeln!(stdin, "isready").unwrap();
+    assert_eq!(lines.next().unwrap().unwrap(), "readyok");
+
+    // Toggling it mid-search must not wait for the search to finish
+    writeln!(stdin, "{}", fen).unwrap();
+    writeln!(stdin, "go infinite").unwrap();
+    assert!(lines.by_ref().map(Result::unwrap).any(|l| l.starts_with("info depth")));
+    writeln!(stdin, "debug on").unwrap();
+    writeln!(stdin, "isready").unwrap();
+    let first = lines.by_ref().map(Result::unwrap).find(|l| l == "readyok" || l.starts_with("bestmove"));
+    assert_eq!(first.as_deref(), Some("readyok"));
+    writeln!(stdin, "stop").unwrap();
+    assert!(lines.by_ref().map(Result::unwrap).any(|l| l.starts_with("bestmove")));
+
+    writeln!(stdin, "quit").unwrap();
+    assert!(engine.wait().unwrap().success());
+}
+
+#[test]
+fn score_perspective_white_reports_from_whites_side() {
+    let (mut engine, mut stdin, mut lines) = spawn_engine();
+    // Black to move and a queen up
+    let mut last_cp = |stdin: &mut ChildStdin| {
+        writeln!(stdin, "position fen 4k3/8/8/3q4/8/8/8/4K3 b - - 0 1").unwrap();
+        writeln!(stdin, "go depth 3").unwrap();
+        let info = lines.by_ref().map(Result::unwrap)
+            .take_while(|l| !l.starts_with("bestmove"))
+            .filter(|l| l.starts_with("info depth"))
+            .last()
+            .unwrap();
+        let mut words = info.split_whitespace().skip_while(|&w| w != "cp").skip(1);
+        words.next().unwrap().parse::<i32>().unwrap()
+    };
+
+    assert!(last_cp(&mut stdin) > 0);
+    writeln!(stdin, "setoption name ScorePerspective value white").unwrap();
+    assert!(last_cp(&mut stdin) < 0);
+
+    writeln!(stdin, "quit").unwrap();
+    assert!(engine.wait().unwrap().success());
+}
+
+#[test]
+fn quit_stops_an_infinite_search_silently() {
+    let (mut engine, mut stdin, mut lines) = spawn_engine();
+    writeln!(stdin, "position fen r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N2N2/PP2BPPP/R2QKB1R w KQ - 0 8").unwrap();
+    writeln!(stdin, "go infinite").unwrap();
+    writeln!(stdin, "isready").unwrap();
+    assert!(lines.by_ref().map(Result::unwrap).any(|l| l == "readyok"));
+
+    writeln!(stdin, "quit").unwrap();
+    let start = std::time::Instant::now();
+    let status = loop {
+        if let Some(status) = engine.try_wait().unwrap() { break status; }
+        assert!(start.elapsed() < std::time::Duration::from_secs(5), "engine still running after quit");
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    };
+    assert!(status.success());
+    assert!(!lines.map(Result::unwrap).any(|l| l.starts_with("bestmove")));
+}
+
+#[test]
+fn closing_stdin_mid_search_acts_like_quit() {
+    let (mut engine, mut stdin, mut lines) = spawn_engine();
+    writeln!(stdin, "position fen r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N2N2/PP2BPPP/R2QKB1R w KQ - 0 8").unwrap();
+    writeln!(stdin, "go depth 64").unwrap();
+    writeln!(stdin, "isready").unwrap();
+    assert!(lines.by_ref().map(Result::unwrap).any(|l| l == "readyok"));
+
+    drop(stdin);
+    let start = std::time::Instant::now();
+    let status = loop {
+        if let Some(status) = engine.try_wait().unwrap() { break status; }
+        assert!(start.elapsed() < std::time::Duration::from_secs(5), "engine still running after EOF");
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    };
+    assert!(status.success());
+    assert!(!lines.map(Result::unwrap).any(|l| l.starts_with("bestmove")));
+}
+
+#[test]
+fn ponder_option_controls_the_bestmove_suffix() {
+    let (mut engine, mut stdin, mut lines) = spawn_engine();
+    let mut bestmove = |stdin: &mut ChildStdin| {
+        writeln!(stdin, "position fen r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N2N2/PP2BPPP/R2QKB1R w KQ - 0 8").unwrap();
+        writeln!(stdin, "go depth 4").unwrap();
+        lines.by_ref().map(Result::unwrap).find(|l| l.starts_with("bestmove")).unwrap()
+    };
+
+    let off = bestmove(&mut stdin);
+    assert_eq!(off.split_whitespace().count(), 2, "{}", off);
+
+    writeln!(stdin, "setoption name Ponder value true").unwrap();
+    let on = bestmove(&mut stdin);
+    let words: Vec<&str> = on.split_whitespace().collect();
+    assert_eq!(words.len(), 4, "{}", on);
+    assert_eq!(words[2], "ponder");
+
+    writeln!(stdin, "quit").unwrap();
+    assert!(engine.wait().unwrap().success());
+}
+
+#[test]
+fn go_ponder_waits_for_ponderhit_or_stop() {
+    let (mut engine, mut stdin, mut lines) = spawn_engine();
+    writeln!(stdin, "setoption name Ponder value true").unwrap();
+    let fen = "position fen r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N2N2/PP2BPPP/R2QKB1R w KQ - 0 8";
+
+    // A 100 ms clock would be used up long before isready; pondering runs on the opponent's time
+    writeln!(stdin, "{}", fen).unwrap();
+    writeln!(stdin, "go ponder wtime 100 btime 100").unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(300));
+    writeln!(stdin, "isready").unwrap();
+    let first = lines.by_ref().map(Result::unwrap).find(|l| l == "readyok" || l.starts_with("bestmove"));
+    assert_eq!(first.as_deref(), Some("readyok"));
+
+    // ponderhit starts the clock, which is short enough to answer on its own
+    writeln!(stdin, "ponderhit").unwrap();
+    assert!(lines.by_ref().map(Result::unwrap).any(|l| l.starts_with("bestmove")));
+
+    // A ponder miss: the GUI stops the search and still gets its bestmove
+    writeln!(stdin, "{}", fen).unwrap();
+    writeln!(stdin, "go ponder wtime 100 btime 100").unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(300));
+    writeln!(stdin, "stop").unwrap();
+    assert!(lines.by_ref().map(Result::unwrap).any(|l| l.starts_with("bestmove")));
+
+    writeln!(stdin, "quit").unwrap();
+    assert!(engine.wait().unwrap().success());
+}
+
+#[test]
+fn setoption_and_ucinewgame_mid_search_stop_it_instead_of_hanging() {
+    let (mut engine, mut stdin, mut lines) = spawn_engine();
+    let fen = "position fen r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N2N2/PP2BPPP/R2QKB1R w KQ - 0 8";
+    for command in ["setoption name Hash value 2", "ucinewgame"] {
+        writeln!(stdin, "{}", fen).unwrap();
+        writeln!(stdin, "go infinite").unwrap();
+        assert!(lines.by_ref().map(Result::unwrap).any(|l| l.starts_with("info depth")));
+        writeln!(stdin, "{}", command).unwrap();
+        writeln!(stdin, "isready").unwrap();
+        let replies: Vec<String> = lines.by_ref().map(Result::unwrap)
+            .filter(|l| l == "readyok" || l.starts_with("bestmove"))
+            .take(2)
+            .collect();
+        assert!(replies[0].starts_with("bestmove"), "{}: {:?}", command, replies);
+        assert_eq!(replies[1], "readyok", "{}", command);
+    }
+
+    writeln!(stdin, "quit").unwrap();
+    assert!(engine.wait().unwrap().success());
+}
+
+#[test]
+fn checkmated_root_reports_bestmove_none() {
+    let (mut engine, mut stdin, mut lines) = spawn_engine();
+    // Fool's mate: White is already mated
+    writeln!(stdin, "position startpos moves f2f3 e7e5 g2g4 d8h4").unwrap();
+    writeln!(stdin, "go depth 5").unwrap();
+    let bestmove = lines.by_ref().map(Result::unwrap).find(|l| l.starts_with("bestmove")).unwrap();
+    assert_eq!(bestmove, "bestmove (none)");
+
+    writeln!(stdin, "quit").unwrap();
+    assert!(engine.wait().unwrap().success());
+}